@@ -1,2 +1,15 @@
 pub const RAR_EXTENSION: &str = "rar";
 pub const ZIP_EXTENSION: &str = "zip";
+pub const SEVEN_ZIP_EXTENSION: &str = "7z";
+pub const TAR_EXTENSION: &str = "tar";
+pub const TAR_GZ_EXTENSION: &str = "tar.gz";
+pub const TAR_ZST_EXTENSION: &str = "tar.zst";
+pub const CBZ_EXTENSION: &str = "cbz";
+pub const CBR_EXTENSION: &str = "cbr";
+pub const EPUB_EXTENSION: &str = "epub";
+pub const PDF_EXTENSION: &str = "pdf";
+pub const COMIC_INFO_FILENAME: &str = "ComicInfo.xml";
+pub const JOURNAL_FILENAME: &str = ".rimg-journal.json";
+pub const INTENT_FILENAME: &str = ".rimg-intent.json";
+pub const MANIFEST_CSV_FILENAME: &str = ".rimg-manifest.csv";
+pub const MANIFEST_JSON_FILENAME: &str = ".rimg-manifest.json";
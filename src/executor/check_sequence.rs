@@ -0,0 +1,314 @@
+use crate::{
+    executor::filter::PathFilter,
+    executor::sort::{sort_natural, sort_natural_paths},
+    executor::utils::{file_name, get_files, is_dir, is_hidden},
+    params::check_sequence::CheckSequenceParams,
+};
+use colored::Colorize;
+use std::collections::BTreeMap;
+use std::{
+    path::{Path, PathBuf},
+    process,
+};
+use walkdir::WalkDir;
+
+/// Problems of the sequential numbers in a directory
+#[derive(Debug, Default, PartialEq)]
+struct SequenceReport {
+    /// Numbers between the initial number and the last number which no file has
+    missing: Vec<u32>,
+    /// Numbers which several files have, with the filenames
+    duplicates: Vec<(u32, Vec<String>)>,
+    /// Files whose number isn't padded with the expected digits
+    padding: Vec<String>,
+    /// Files which don't follow the sequential scheme
+    unexpected: Vec<String>,
+}
+
+impl SequenceReport {
+    /// Returns the number of problems
+    fn count(&self) -> usize {
+        self.missing.len() + self.duplicates.len() + self.padding.len() + self.unexpected.len()
+    }
+}
+
+/// Check the sequential numbers of the files in each directory
+///
+/// # Arguments
+///
+/// * `params` - Check sequence params
+pub fn execute(params: &CheckSequenceParams) {
+    let filter = PathFilter::new(&params.input_dir, &params.include, &params.exclude)
+        .expect("patterns are validated by the parser");
+
+    let mut walker = WalkDir::new(&params.input_dir);
+    if let Some(v) = params.min_depth {
+        walker = walker.min_depth(v);
+    }
+    if let Some(v) = params.max_depth {
+        walker = walker.max_depth(v);
+    }
+
+    // Excluded directories are skipped with their subdirectories
+    let mut directories = walker
+        .into_iter()
+        .filter_entry(|e| !(is_dir(e) && filter.is_excluded(e.path())))
+        .filter_map(Result::ok)
+        .filter(|e| is_dir(e) && !is_hidden(e))
+        .map(|e| e.into_path())
+        .collect::<Vec<PathBuf>>();
+
+    sort_natural_paths(&mut directories);
+
+    let total_count = directories.len();
+    println!("{total_count} directories will be checked");
+
+    let extension_types = params.extensions.join(", ").to_lowercase();
+    let mut sequential_count = 0;
+    let mut problem_directories = vec![];
+    for directory in directories {
+        let dir_name = file_name(&directory);
+        let names = get_target_names(&directory, &filter, params);
+        if names.is_empty() {
+            println!("There are no {extension_types} files in {dir_name} directory");
+            continue;
+        }
+
+        let report = check_names(&names, params);
+        if report.count() == 0 {
+            println!("{dir_name} directory is sequential ({} files)", names.len());
+            sequential_count += 1;
+            continue;
+        }
+
+        eprintln!(
+            "{}",
+            format!("{dir_name} directory has {} problems", report.count())
+                .red()
+                .bold()
+        );
+        print_report(&report, params.digit as usize);
+        problem_directories.push(directory);
+    }
+
+    // Show check result
+    let skipped_count = total_count - sequential_count - problem_directories.len();
+    println!(
+        "Check result: {}/{}/{}/{} = {}/{}/{}/{}",
+        "Total".blue().bold(),
+        "Sequential".green().bold(),
+        "Skipped".yellow().bold(),
+        "Problem".red().bold(),
+        format!("{total_count}").blue().bold(),
+        format!("{sequential_count}").green().bold(),
+        format!("{skipped_count}").yellow().bold(),
+        format!("{}", problem_directories.len()).red().bold(),
+    );
+
+    // Show problem directories
+    if !problem_directories.is_empty() {
+        println!(
+            "{}",
+            "The problem directories are listed below".red().bold()
+        );
+        for directory in problem_directories {
+            println!("{}", directory.display());
+        }
+        process::exit(1);
+    }
+}
+
+/// Returns the names of the target files in the given directory in natural order
+///
+/// # Arguments
+///
+/// * `directory` - Directory
+/// * `filter` - Include and exclude patterns
+/// * `params` - Check sequence params
+fn get_target_names(
+    directory: &Path,
+    filter: &PathFilter,
+    params: &CheckSequenceParams,
+) -> Vec<String> {
+    let mut files = get_files(directory, &params.extensions, |v| filter.is_included(v));
+
+    sort_natural(&mut files);
+
+    files.iter().map(|v| file_name(v)).collect()
+}
+
+/// Print the problems of a directory
+///
+/// # Arguments
+///
+/// * `report` - Problems of the directory
+/// * `digit` - Number of digits
+fn print_report(report: &SequenceReport, digit: usize) {
+    if !report.missing.is_empty() {
+        let numbers = report
+            .missing
+            .iter()
+            .map(|v| format!("{v:0digit$}"))
+            .collect::<Vec<String>>();
+        println!("  Missing: {}", numbers.join(", "));
+    }
+    for (number, names) in &report.duplicates {
+        println!("  Duplicated {number:0digit$}: {}", names.join(", "));
+    }
+    if !report.padding.is_empty() {
+        println!("  Wrong padding: {}", report.padding.join(", "));
+    }
+    if !report.unexpected.is_empty() {
+        println!("  Not sequential: {}", report.unexpected.join(", "));
+    }
+}
+
+/// Returns the problems of the sequential numbers of the given filenames
+/// A filename is a number such as `0012.jpg`, or two numbers of a spread such as `0012-0013.jpg`
+///
+/// # Arguments
+///
+/// * `names` - Filenames in natural order
+/// * `params` - Check sequence params
+fn check_names(names: &[String], params: &CheckSequenceParams) -> SequenceReport {
+    let digit = params.digit as usize;
+    let initial = params.initial;
+    let step = params.step as u32;
+
+    let mut report = SequenceReport::default();
+    let mut numbers = BTreeMap::<u32, Vec<String>>::new();
+    for name in names {
+        let stem = Path::new(name)
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy();
+
+        let Some(parts) = parse_numbers(&stem) else {
+            report.unexpected.push(name.clone());
+            continue;
+        };
+
+        // Every number must be on the sequence and a spread must have consecutive numbers
+        let on_sequence = parts
+            .iter()
+            .all(|(v, _)| *v >= initial && (*v - initial).is_multiple_of(step));
+        let consecutive = parts
+            .windows(2)
+            .all(|v| v[0].0.checked_add(step) == Some(v[1].0));
+        if !on_sequence || !consecutive {
+            report.unexpected.push(name.clone());
+            continue;
+        }
+
+        if parts
+            .iter()
+            .any(|(v, s)| s.len() != digit.max(v.to_string().len()))
+        {
+            report.padding.push(name.clone());
+        }
+
+        for (v, _) in parts {
+            numbers.entry(v).or_default().push(name.clone());
+        }
+    }
+
+    if let Some(&last) = numbers.keys().next_back() {
+        report.missing = (initial..=last)
+            .step_by(step as usize)
+            .filter(|v| !numbers.contains_key(v))
+            .collect();
+    }
+
+    report.duplicates = numbers.into_iter().filter(|(_, v)| v.len() > 1).collect();
+
+    report
+}
+
+/// Returns the numbers of the given stem with their text, or None if it isn't numbers
+/// A stem has one number, or two numbers joined by `-` for a spread
+///
+/// # Arguments
+///
+/// * `stem` - Filename without extension
+pub fn parse_numbers(stem: &str) -> Option<Vec<(u32, &str)>> {
+    let parts = stem.split('-').collect::<Vec<&str>>();
+    if parts.len() > 2 {
+        return None;
+    }
+
+    parts
+        .into_iter()
+        .map(|v| {
+            if v.is_empty() || !v.chars().all(|c| c.is_ascii_digit()) {
+                return None;
+            }
+            v.parse::<u32>().ok().map(|n| (n, v))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    fn check(names: &[&str], args: &[&str]) -> SequenceReport {
+        let params = CheckSequenceParams::parse_from([&["check-sequence", "."], args].concat());
+        let names = names.iter().map(|v| v.to_string()).collect::<Vec<String>>();
+        check_names(&names, &params)
+    }
+
+    #[test]
+    fn sequential_files_should_have_no_problems() {
+        let report = check(&["0001.jpg", "0002.jpg", "0003-0004.jpg", "0005.jpg"], &[]);
+        assert_eq!(SequenceReport::default(), report);
+    }
+
+    #[test]
+    fn problems_should_be_reported() {
+        let report = check(
+            &["0001.jpg", "02.jpg", "0005.jpg", "0005.png", "cover.jpg"],
+            &[],
+        );
+        assert_eq!(
+            SequenceReport {
+                missing: vec![3, 4],
+                duplicates: vec![(5, vec!["0005.jpg".to_string(), "0005.png".to_string()])],
+                padding: vec!["02.jpg".to_string()],
+                unexpected: vec!["cover.jpg".to_string()],
+            },
+            report
+        );
+    }
+
+    #[test]
+    fn numbers_off_the_step_should_not_be_sequential() {
+        let report = check(
+            &["000.jpg", "002.jpg", "003.jpg"],
+            &["-d", "3", "-i", "0", "-s", "2"],
+        );
+        assert_eq!(vec!["003.jpg".to_string()], report.unexpected);
+        assert!(report.missing.is_empty());
+    }
+
+    #[test]
+    fn only_included_files_should_be_checked() {
+        let dir = tempfile::TempDir::new().unwrap();
+        for name in ["0001.jpg", "0002.jpg", "cover.jpg"] {
+            std::fs::write(dir.path().join(name), name).unwrap();
+        }
+        let input_dir = dir.path().to_string_lossy().to_string();
+        let params = CheckSequenceParams::parse_from([
+            "check-sequence",
+            input_dir.as_str(),
+            "--include",
+            "0*",
+        ]);
+        let filter = PathFilter::new(&params.input_dir, &params.include, &params.exclude).unwrap();
+
+        assert_eq!(
+            vec!["0001.jpg".to_string(), "0002.jpg".to_string()],
+            get_target_names(dir.path(), &filter, &params)
+        );
+    }
+}
@@ -0,0 +1,154 @@
+use crate::executor::sort::sort_natural;
+use crate::executor::utils::{file_name, is_file, is_hidden};
+use crate::params::compress::ReadingDirection;
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// Page of a comic
+pub struct ComicPage {
+    pub path: PathBuf,
+    pub width: usize,
+    pub height: usize,
+    pub size: u64,
+}
+
+/// Returns the images under the given directory as pages in natural order
+/// Files which aren't images are skipped
+///
+/// # Arguments
+///
+/// * `directory` - Directory
+pub fn get_pages(directory: &Path) -> Vec<ComicPage> {
+    let mut files = WalkDir::new(directory)
+        .into_iter()
+        .filter_entry(|v| !is_hidden(v))
+        .filter_map(Result::ok)
+        .filter(is_file)
+        .map(|v| v.into_path())
+        .collect::<Vec<PathBuf>>();
+
+    sort_natural(&mut files);
+
+    files
+        .into_iter()
+        .filter_map(|path| {
+            let dimension = imagesize::size(&path).ok()?;
+            let size = fs::metadata(&path).ok()?.len();
+            Some(ComicPage {
+                path,
+                width: dimension.width,
+                height: dimension.height,
+                size,
+            })
+        })
+        .collect()
+}
+
+/// Returns the content of ComicInfo.xml of the given directory
+/// The series and the number come from the directory name such as `Series 012`,
+/// and the series is the parent directory name if the directory name is just a number
+///
+/// # Arguments
+///
+/// * `directory` - Directory
+/// * `pages` - Pages of the directory
+/// * `direction` - Reading direction
+pub fn comic_info(directory: &Path, pages: &[ComicPage], direction: ReadingDirection) -> String {
+    let (mut series, number) = split_series(&file_name(directory));
+    if series.is_empty() {
+        series = directory.parent().map(file_name).unwrap_or_default();
+    }
+    let manga = match direction {
+        ReadingDirection::Ltr => "Unknown",
+        ReadingDirection::Rtl => "YesAndRightToLeft",
+    };
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    xml.push_str("<ComicInfo xmlns:xsd=\"http://www.w3.org/2001/XMLSchema\" xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\">\n");
+    let _ = writeln!(xml, "  <Series>{}</Series>", escape(&series));
+    if let Some(v) = number {
+        let _ = writeln!(xml, "  <Number>{v}</Number>");
+    }
+    let _ = writeln!(xml, "  <PageCount>{}</PageCount>", pages.len());
+    let _ = writeln!(xml, "  <Manga>{manga}</Manga>");
+    xml.push_str("  <Pages>\n");
+    for (i, page) in pages.iter().enumerate() {
+        let page_type = if i == 0 { " Type=\"FrontCover\"" } else { "" };
+        let _ = writeln!(
+            xml,
+            "    <Page Image=\"{i}\"{page_type} ImageSize=\"{}\" ImageWidth=\"{}\" ImageHeight=\"{}\" />",
+            page.size, page.width, page.height
+        );
+    }
+    xml.push_str("  </Pages>\n");
+    xml.push_str("</ComicInfo>\n");
+
+    xml
+}
+
+/// Returns the series and the number of the given directory name
+/// The number is the trailing digits without leading zeros
+///
+/// # Arguments
+///
+/// * `name` - Directory name
+fn split_series(name: &str) -> (String, Option<String>) {
+    let series = name.trim_end_matches(|c: char| c.is_ascii_digit());
+    if series.len() == name.len() {
+        return (name.trim().to_string(), None);
+    }
+
+    let digits = name[series.len()..].trim_start_matches('0');
+    let number = if digits.is_empty() { "0" } else { digits };
+    let series = series.trim_end_matches(|c: char| c.is_whitespace() || "_-#.".contains(c));
+
+    (series.to_string(), Some(number.to_string()))
+}
+
+/// Returns the text escaped for XML
+///
+/// # Arguments
+///
+/// * `text` - Text
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trailing_number_should_be_split_from_series() {
+        assert_eq!(
+            ("One Piece".to_string(), Some("12".to_string())),
+            split_series("One Piece - 012")
+        );
+        assert_eq!(("Extra".to_string(), None), split_series("Extra"));
+        assert_eq!((String::new(), Some("0".to_string())), split_series("000"));
+    }
+
+    #[test]
+    fn comic_info_should_have_pages() {
+        let pages = [ComicPage {
+            path: PathBuf::from("Tom & Jerry 3/01.jpg"),
+            width: 800,
+            height: 1200,
+            size: 1024,
+        }];
+        let xml = comic_info(Path::new("Tom & Jerry 3"), &pages, ReadingDirection::Rtl);
+        assert!(xml.contains("<Series>Tom &amp; Jerry</Series>"));
+        assert!(xml.contains("<Number>3</Number>"));
+        assert!(xml.contains("<PageCount>1</PageCount>"));
+        assert!(xml.contains("<Manga>YesAndRightToLeft</Manga>"));
+        assert!(xml.contains(
+            "<Page Image=\"0\" Type=\"FrontCover\" ImageSize=\"1024\" ImageWidth=\"800\" ImageHeight=\"1200\" />"
+        ));
+    }
+}
@@ -1,13 +1,22 @@
-use crate::constants::file::{RAR_EXTENSION, ZIP_EXTENSION};
+use crate::constants::file::{
+    CBR_EXTENSION, CBZ_EXTENSION, COMIC_INFO_FILENAME, EPUB_EXTENSION, PDF_EXTENSION,
+    RAR_EXTENSION, SEVEN_ZIP_EXTENSION, TAR_EXTENSION, TAR_GZ_EXTENSION, TAR_ZST_EXTENSION,
+    ZIP_EXTENSION,
+};
+use crate::executor::comic_info::{comic_info, get_pages, ComicPage};
+use crate::executor::epub;
+use crate::executor::pdf;
 use crate::executor::utils::{ask, get_progress_bar, have_extension, is_dir, is_hidden, is_parent};
 use crate::params::compress::CompressParams;
 use colored::Colorize;
 use execute::Execute;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
 use indicatif::ProgressBar;
 use rayon::prelude::*;
+use sevenz_rust2::{ArchiveEntry, ArchiveReader, ArchiveWriter, Password};
 use std::collections::HashMap;
 use std::fs::File;
-use std::io::{Read, Write};
+use std::io::{self, Read, Write};
 use std::path::{Path, PathBuf};
 use std::process::{self, Command};
 use std::sync::{Arc, Mutex};
@@ -22,7 +31,7 @@ use zip::{CompressionMethod, ZipArchive, ZipWriter};
 /// * `params` - Compress params
 pub fn execute(params: &CompressParams) {
     // Check rar executable
-    if params.format_type == RAR_EXTENSION
+    if [RAR_EXTENSION, CBR_EXTENSION].contains(&params.format_type.as_str())
         && Command::new(RAR_EXTENSION)
             .execute_check_exit_status_code(0)
             .is_err()
@@ -103,6 +112,78 @@ fn compress_files(params: &CompressParams, directories: &Vec<DirEntry>) -> HashM
     match params.format_type.as_str() {
         RAR_EXTENSION => compress_rar(directories, params, &bar, &success_files, &error_files),
         ZIP_EXTENSION => compress_zip(directories, params, &bar, &success_files, &error_files),
+        SEVEN_ZIP_EXTENSION => compress_with(
+            directories,
+            params,
+            &bar,
+            &success_files,
+            &error_files,
+            SEVEN_ZIP_EXTENSION,
+            compress_7z,
+        ),
+        TAR_EXTENSION => compress_with(
+            directories,
+            params,
+            &bar,
+            &success_files,
+            &error_files,
+            TAR_EXTENSION,
+            compress_tar,
+        ),
+        TAR_GZ_EXTENSION => compress_with(
+            directories,
+            params,
+            &bar,
+            &success_files,
+            &error_files,
+            TAR_GZ_EXTENSION,
+            compress_tar_gz,
+        ),
+        TAR_ZST_EXTENSION => compress_with(
+            directories,
+            params,
+            &bar,
+            &success_files,
+            &error_files,
+            TAR_ZST_EXTENSION,
+            compress_tar_zst,
+        ),
+        CBZ_EXTENSION => compress_with(
+            directories,
+            params,
+            &bar,
+            &success_files,
+            &error_files,
+            CBZ_EXTENSION,
+            compress_cbz,
+        ),
+        CBR_EXTENSION => compress_with(
+            directories,
+            params,
+            &bar,
+            &success_files,
+            &error_files,
+            CBR_EXTENSION,
+            compress_cbr,
+        ),
+        EPUB_EXTENSION => compress_with(
+            directories,
+            params,
+            &bar,
+            &success_files,
+            &error_files,
+            EPUB_EXTENSION,
+            compress_epub,
+        ),
+        PDF_EXTENSION => compress_with(
+            directories,
+            params,
+            &bar,
+            &success_files,
+            &error_files,
+            PDF_EXTENSION,
+            compress_pdf,
+        ),
         _ => unimplemented!(),
     }
 
@@ -142,6 +223,7 @@ fn compress_files(params: &CompressParams, directories: &Vec<DirEntry>) -> HashM
 /// * `bar` - Progress bar
 /// * `success_files` - Successfully created files
 /// * `error_files` - Error files
+#[allow(clippy::collapsible_match)]
 fn compress_rar(
     directories: &Vec<DirEntry>,
     params: &CompressParams,
@@ -281,6 +363,334 @@ fn compress_zip(
     }
 }
 
+/// Compress each directory to a file in parallel by the given function
+///
+/// # Arguments
+///
+/// * `directories` - Directories to compress
+/// * `params` - Compress params
+/// * `bar` - Progress bar
+/// * `success_files` - Successfully created files
+/// * `error_files` - Error files
+/// * `extension` - Extension of the output files
+/// * `compress` - Compress a directory to the output filepath
+fn compress_with(
+    directories: &Vec<DirEntry>,
+    params: &CompressParams,
+    bar: &ProgressBar,
+    success_files: &Arc<Mutex<HashMap<String, bool>>>,
+    error_files: &Arc<Mutex<Vec<String>>>,
+    extension: &str,
+    compress: fn(&DirEntry, &Path, &CompressParams) -> Result<(), String>,
+) {
+    directories.par_iter().for_each(|directory| {
+        let output_filepath = _get_output_filepath(params, directory, extension);
+        let output_filename = output_filepath.file_name().unwrap().to_string_lossy();
+        bar.set_message(format!("Compressing {}", &output_filename));
+
+        match compress(directory, &output_filepath, params) {
+            Ok(_) => {
+                let mut success_files = success_files.lock().unwrap();
+                success_files.insert(output_filename.to_string(), false);
+                bar.set_message(format!("Compressed {}!", &output_filename));
+            }
+            Err(e) => {
+                bar.suspend(|| eprintln!("filename: {output_filename}, error: {e}"));
+                let mut error_files = error_files.lock().unwrap();
+                error_files.push(output_filename.to_string());
+                bar.set_message(format!("Failed to compress {}!", &output_filename));
+            }
+        }
+
+        bar.inc(1);
+    });
+}
+
+/// Compress a directory to 7z
+///
+/// # Arguments
+///
+/// * `directory` - Directory to compress
+/// * `output_filepath` - Output filepath
+/// * `params` - Compress params
+fn compress_7z(
+    directory: &DirEntry,
+    output_filepath: &Path,
+    _params: &CompressParams,
+) -> Result<(), String> {
+    let mut writer = ArchiveWriter::create(output_filepath).map_err(|e| e.to_string())?;
+
+    for entry in _get_path_entries(directory) {
+        let entry_filename = entry
+            .strip_prefix(directory.path())
+            .map_err(|e| e.to_string())?
+            .to_string_lossy()
+            .replace('\\', "/");
+        let archive_entry = ArchiveEntry::from_path(&entry, entry_filename);
+
+        if entry.is_file() {
+            let file = File::open(&entry).map_err(|e| e.to_string())?;
+            writer
+                .push_archive_entry(archive_entry, Some(file))
+                .map_err(|e| e.to_string())?;
+        } else if entry.is_dir() {
+            writer
+                .push_archive_entry::<File>(archive_entry, None)
+                .map_err(|e| e.to_string())?;
+        }
+    }
+
+    writer.finish().map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+/// Compress a directory to tar
+///
+/// # Arguments
+///
+/// * `directory` - Directory to compress
+/// * `output_filepath` - Output filepath
+/// * `params` - Compress params
+fn compress_tar(
+    directory: &DirEntry,
+    output_filepath: &Path,
+    _params: &CompressParams,
+) -> Result<(), String> {
+    let file = File::create(output_filepath).map_err(|e| e.to_string())?;
+    write_tar(directory, file)?
+        .sync_all()
+        .map_err(|e| e.to_string())
+}
+
+/// Compress a directory to tar.gz
+///
+/// # Arguments
+///
+/// * `directory` - Directory to compress
+/// * `output_filepath` - Output filepath
+/// * `params` - Compress params
+fn compress_tar_gz(
+    directory: &DirEntry,
+    output_filepath: &Path,
+    _params: &CompressParams,
+) -> Result<(), String> {
+    let file = File::create(output_filepath).map_err(|e| e.to_string())?;
+    write_tar(directory, GzEncoder::new(file, Compression::best()))?
+        .finish()
+        .and_then(|v| v.sync_all())
+        .map_err(|e| e.to_string())
+}
+
+/// Compress a directory to tar.zst with the zstd level of the params
+///
+/// # Arguments
+///
+/// * `directory` - Directory to compress
+/// * `output_filepath` - Output filepath
+/// * `params` - Compress params
+fn compress_tar_zst(
+    directory: &DirEntry,
+    output_filepath: &Path,
+    params: &CompressParams,
+) -> Result<(), String> {
+    let file = File::create(output_filepath).map_err(|e| e.to_string())?;
+    let encoder = zstd::Encoder::new(file, params.zstd_level).map_err(|e| e.to_string())?;
+    write_tar(directory, encoder)?
+        .finish()
+        .and_then(|v| v.sync_all())
+        .map_err(|e| e.to_string())
+}
+
+/// Compress a directory to cbz with ComicInfo.xml
+/// The images are stored without compression as they are already compressed
+///
+/// # Arguments
+///
+/// * `directory` - Directory to compress
+/// * `output_filepath` - Output filepath
+/// * `params` - Compress params
+fn compress_cbz(
+    directory: &DirEntry,
+    output_filepath: &Path,
+    params: &CompressParams,
+) -> Result<(), String> {
+    let pages = get_pages(directory.path());
+    if pages.is_empty() {
+        return Err("There are no images".to_string());
+    }
+
+    let file = File::create(output_filepath).map_err(|e| e.to_string())?;
+    let mut zip = ZipWriter::new(file);
+    let zip_options = SimpleFileOptions::default()
+        .compression_method(CompressionMethod::Stored)
+        .unix_permissions(0o644);
+
+    zip.start_file(COMIC_INFO_FILENAME, zip_options)
+        .map_err(|e| e.to_string())?;
+    zip.write_all(comic_info(directory.path(), &pages, params.direction).as_bytes())
+        .map_err(|e| e.to_string())?;
+
+    for page in &pages {
+        let entry_filename = page
+            .path
+            .strip_prefix(directory.path())
+            .map_err(|e| e.to_string())?
+            .to_string_lossy()
+            .replace('\\', "/");
+        zip.start_file(entry_filename, zip_options)
+            .map_err(|e| e.to_string())?;
+        let mut f = File::open(&page.path).map_err(|e| e.to_string())?;
+        io::copy(&mut f, &mut zip).map_err(|e| e.to_string())?;
+    }
+
+    zip.finish().map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+/// Compress a directory to cbr with ComicInfo.xml by the rar executable
+///
+/// # Arguments
+///
+/// * `directory` - Directory to compress
+/// * `output_filepath` - Output filepath
+/// * `params` - Compress params
+fn compress_cbr(
+    directory: &DirEntry,
+    output_filepath: &Path,
+    params: &CompressParams,
+) -> Result<(), String> {
+    let pages = get_pages(directory.path());
+    if pages.is_empty() {
+        return Err("There are no images".to_string());
+    }
+
+    // rar runs in the directory, so the output filepath must not be relative
+    let output_filepath = std::path::absolute(output_filepath).map_err(|e| e.to_string())?;
+    let mut args = ["a", "-m5", "--"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect::<Vec<String>>();
+    args.push(output_filepath.to_string_lossy().to_string());
+    for page in &pages {
+        let entry_filename = page
+            .path
+            .strip_prefix(directory.path())
+            .map_err(|e| e.to_string())?;
+        args.push(entry_filename.to_string_lossy().to_string());
+    }
+
+    let mut command = Command::new(RAR_EXTENSION);
+    command.args(args);
+    command.current_dir(directory.path());
+    match command.execute() {
+        Ok(Some(0)) => {}
+        Ok(v) => return Err(format!("rar exited with {v:?}")),
+        Err(e) => return Err(e.to_string()),
+    }
+
+    // ComicInfo.xml is given from stdin
+    let mut command = Command::new(RAR_EXTENSION);
+    command.args([
+        "a".to_string(),
+        format!("-si{COMIC_INFO_FILENAME}"),
+        "--".to_string(),
+        output_filepath.to_string_lossy().to_string(),
+    ]);
+    match command.execute_input(&comic_info(directory.path(), &pages, params.direction)) {
+        Ok(Some(0)) => Ok(()),
+        Ok(v) => Err(format!("rar exited with {v:?}")),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Compress a directory to fixed-layout EPUB with one page per image
+/// Images which EPUB doesn't support are skipped
+///
+/// # Arguments
+///
+/// * `directory` - Directory to compress
+/// * `output_filepath` - Output filepath
+/// * `params` - Compress params
+fn compress_epub(
+    directory: &DirEntry,
+    output_filepath: &Path,
+    params: &CompressParams,
+) -> Result<(), String> {
+    let pages = get_pages(directory.path())
+        .into_iter()
+        .filter(|v| epub::media_type(&v.path).is_some())
+        .collect::<Vec<_>>();
+    if pages.is_empty() {
+        return Err("There are no images".to_string());
+    }
+
+    epub::write(directory.path(), output_filepath, &pages, params.direction)
+}
+
+/// Compress a directory to PDF with one page per image
+/// Images other than JPEG and PNG are skipped
+///
+/// # Arguments
+///
+/// * `directory` - Directory to compress
+/// * `output_filepath` - Output filepath
+/// * `params` - Compress params
+fn compress_pdf(
+    directory: &DirEntry,
+    output_filepath: &Path,
+    _params: &CompressParams,
+) -> Result<(), String> {
+    let pages = get_pdf_pages(directory.path());
+    if pages.is_empty() {
+        return Err("There are no images".to_string());
+    }
+
+    pdf::write(&pages, output_filepath)
+}
+
+/// Returns the images under the given directory which can be pages of PDF
+///
+/// # Arguments
+///
+/// * `directory` - Directory
+fn get_pdf_pages(directory: &Path) -> Vec<ComicPage> {
+    get_pages(directory)
+        .into_iter()
+        .filter(|v| pdf::is_supported(&v.path))
+        .collect()
+}
+
+/// Write the files/directories under the given directory as tar and returns the writer
+///
+/// # Arguments
+///
+/// * `directory` - Directory to compress
+/// * `writer` - Writer of the tar
+fn write_tar<W: Write>(directory: &DirEntry, writer: W) -> Result<W, String> {
+    let mut builder = tar::Builder::new(writer);
+
+    for entry in _get_path_entries(directory) {
+        let entry_filename = entry
+            .strip_prefix(directory.path())
+            .map_err(|e| e.to_string())?
+            .to_owned();
+
+        if entry.is_file() {
+            builder
+                .append_path_with_name(&entry, &entry_filename)
+                .map_err(|e| e.to_string())?;
+        } else if entry.is_dir() {
+            builder
+                .append_dir(&entry_filename, &entry)
+                .map_err(|e| e.to_string())?;
+        }
+    }
+
+    builder.into_inner().map_err(|e| e.to_string())
+}
+
 /// Returns output filepath
 /// If the output_dir was specified, use output_dir for the output filepath
 /// Otherwise, input_dir is used
@@ -359,8 +769,25 @@ fn validate_files(params: &CompressParams, files: &HashMap<String, bool>) {
     let bar = get_progress_bar(files.len() as u64);
 
     let validation_result = match params.format_type.as_str() {
-        RAR_EXTENSION => validate_rar(files, output_dir, &bar),
-        ZIP_EXTENSION => validate_zip(files, output_dir, &bar),
+        RAR_EXTENSION | CBR_EXTENSION => validate_rar(files, output_dir, &bar),
+        ZIP_EXTENSION | CBZ_EXTENSION => validate_zip(files, output_dir, &bar),
+        EPUB_EXTENSION => validate_with(files, output_dir, &bar, validate_epub_entries),
+        PDF_EXTENSION => validate_with(files, output_dir, &bar, |v| {
+            validate_pdf_pages(v, &params.input_dir)
+        }),
+        SEVEN_ZIP_EXTENSION => validate_with(files, output_dir, &bar, validate_7z_entries),
+        TAR_EXTENSION => validate_with(files, output_dir, &bar, |v| {
+            validate_tar_entries(File::open(v).map_err(|e| e.to_string())?)
+        }),
+        TAR_GZ_EXTENSION => validate_with(files, output_dir, &bar, |v| {
+            validate_tar_entries(GzDecoder::new(File::open(v).map_err(|e| e.to_string())?))
+        }),
+        TAR_ZST_EXTENSION => validate_with(files, output_dir, &bar, |v| {
+            validate_tar_entries(
+                zstd::Decoder::new(File::open(v).map_err(|e| e.to_string())?)
+                    .map_err(|e| e.to_string())?,
+            )
+        }),
         _ => unimplemented!(),
     };
 
@@ -403,6 +830,7 @@ fn validate_files(params: &CompressParams, files: &HashMap<String, bool>) {
 /// # Returns
 ///
 /// A new HashMap containing the validation result
+#[allow(clippy::collapsible_match)]
 fn validate_rar(
     files: &HashMap<String, bool>,
     current_dir: &str,
@@ -442,6 +870,50 @@ fn validate_rar(
     validated_files
 }
 
+/// Validate files in parallel by the given function
+///
+/// # Arguments
+///
+/// * `files` - Filepaths to validate
+/// * `current_dir` - Current directory
+/// * `bar` - Progress bar
+/// * `validate` - Validate a file
+///
+/// # Returns
+///
+/// A new HashMap containing the validation result
+fn validate_with<F>(
+    files: &HashMap<String, bool>,
+    current_dir: &str,
+    bar: &ProgressBar,
+    validate: F,
+) -> HashMap<String, bool>
+where
+    F: Fn(&Path) -> Result<bool, String> + Sync,
+{
+    let validated_files = Arc::new(Mutex::new(files.clone()));
+
+    files.par_iter().for_each(|(filename, _)| {
+        let fullpath = Path::new(current_dir).join(filename);
+        bar.set_message(format!("Validating {filename}"));
+
+        let is_valid = match validate(&fullpath) {
+            Ok(v) => v,
+            Err(e) => {
+                bar.suspend(|| eprintln!("filename: {filename}, error: {e}"));
+                false
+            }
+        };
+
+        let mut validated_files = validated_files.lock().unwrap();
+        validated_files.insert(filename.to_string(), is_valid);
+        bar.inc(1);
+    });
+
+    let validated_files = validated_files.lock().unwrap().clone();
+    validated_files
+}
+
 /// Validate zip files
 ///
 /// # Arguments
@@ -510,3 +982,88 @@ fn validate_zip_entries(filepath: &PathBuf) -> Result<bool, String> {
 
     Ok(true)
 }
+
+/// Check the mimetype is the first entry without compression,
+/// then decompress every entry of the EPUB
+///
+/// # Arguments
+///
+/// * `filepath` - EPUB filepath
+fn validate_epub_entries(filepath: &Path) -> Result<bool, String> {
+    let file = File::open(filepath).map_err(|e| e.to_string())?;
+    let mut archive = ZipArchive::new(file).map_err(|e| e.to_string())?;
+
+    let mut mimetype = archive.by_index(0).map_err(|e| e.to_string())?;
+    let mut content = String::new();
+    mimetype
+        .read_to_string(&mut content)
+        .map_err(|e| e.to_string())?;
+    if mimetype.name() != "mimetype"
+        || mimetype.compression() != CompressionMethod::Stored
+        || content != "application/epub+zip"
+    {
+        return Err("The first entry isn't the uncompressed mimetype".to_string());
+    }
+
+    validate_zip_entries(&filepath.to_path_buf())
+}
+
+/// Parse the PDF and check it has as many pages as the images of its source directory,
+/// which is the directory of the same name under the input directory
+/// Only the parse is checked if the source directory isn't found, such as with `--validate-only`
+///
+/// # Arguments
+///
+/// * `filepath` - PDF filepath
+/// * `input_dir` - Input directory
+fn validate_pdf_pages(filepath: &Path, input_dir: &str) -> Result<bool, String> {
+    let actual = pdf::page_count(filepath)?;
+    let source = Path::new(input_dir).join(filepath.file_stem().unwrap_or_default());
+    if !source.is_dir() {
+        return Ok(true);
+    }
+
+    let expected = get_pdf_pages(&source).len();
+    if actual != expected {
+        return Err(format!("{actual} pages for {expected} images"));
+    }
+
+    Ok(true)
+}
+
+/// Decompress every entry of the 7z file, whose CRC is checked by the reader
+///
+/// # Arguments
+///
+/// * `filepath` - 7z filepath
+fn validate_7z_entries(filepath: &Path) -> Result<bool, String> {
+    let mut reader = ArchiveReader::open(filepath, Password::empty()).map_err(|e| e.to_string())?;
+
+    reader
+        .for_each_entries(|_, entry_reader| {
+            io::copy(entry_reader, &mut io::sink())?;
+            Ok(true)
+        })
+        .map_err(|e| e.to_string())?;
+
+    Ok(true)
+}
+
+/// Decompress and walk every entry of the tar,
+/// then read the rest of the stream so that the checksum of the compression is checked
+///
+/// # Arguments
+///
+/// * `reader` - Reader of the tar
+fn validate_tar_entries<R: Read>(reader: R) -> Result<bool, String> {
+    let mut archive = tar::Archive::new(reader);
+
+    for entry in archive.entries().map_err(|e| e.to_string())? {
+        let mut entry = entry.map_err(|e| e.to_string())?;
+        io::copy(&mut entry, &mut io::sink()).map_err(|e| e.to_string())?;
+    }
+
+    io::copy(&mut archive.into_inner(), &mut io::sink()).map_err(|e| e.to_string())?;
+
+    Ok(true)
+}
@@ -0,0 +1,336 @@
+use crate::executor::comic_info::{escape, ComicPage};
+use crate::executor::utils::{civil_date, file_name};
+use crate::params::compress::ReadingDirection;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+const MIMETYPE: &str = "application/epub+zip";
+
+const CONTAINER_XML: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles>
+    <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+  </rootfiles>
+</container>
+"#;
+
+/// Image of a page in the EPUB
+struct EpubImage<'a> {
+    page: &'a ComicPage,
+    /// Filename of the image in the EPUB
+    image: String,
+    /// Filename of the XHTML which shows the image
+    xhtml: String,
+    media_type: &'static str,
+}
+
+/// Returns the media type of the given image, or None if EPUB doesn't support it
+///
+/// # Arguments
+///
+/// * `path` - Image path
+pub fn media_type(path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_string_lossy().to_lowercase();
+    match extension.as_str() {
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "png" => Some("image/png"),
+        "gif" => Some("image/gif"),
+        "webp" => Some("image/webp"),
+        _ => None,
+    }
+}
+
+/// Write a fixed-layout EPUB 3 with one page per image
+/// The first page is the cover
+///
+/// # Arguments
+///
+/// * `directory` - Directory of the images
+/// * `output_filepath` - Output filepath
+/// * `pages` - Pages in reading order, whose images are supported by EPUB
+/// * `direction` - Page progression direction
+pub fn write(
+    directory: &Path,
+    output_filepath: &Path,
+    pages: &[ComicPage],
+    direction: ReadingDirection,
+) -> Result<(), String> {
+    let images = get_images(pages)?;
+    let title = file_name(directory);
+
+    let file = File::create(output_filepath).map_err(|e| e.to_string())?;
+    let mut zip = ZipWriter::new(file);
+    let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+    let deflated = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    // The mimetype must be the first entry without compression
+    let mut entries = vec![
+        ("mimetype".to_string(), MIMETYPE.to_string(), stored),
+        (
+            "META-INF/container.xml".to_string(),
+            CONTAINER_XML.to_string(),
+            deflated,
+        ),
+        (
+            "OEBPS/content.opf".to_string(),
+            package_document(&title, &images, direction),
+            deflated,
+        ),
+        (
+            "OEBPS/nav.xhtml".to_string(),
+            navigation_document(&title, &images),
+            deflated,
+        ),
+    ];
+    for (i, image) in images.iter().enumerate() {
+        entries.push((
+            format!("OEBPS/{}", image.xhtml),
+            page_document(&title, i, image),
+            deflated,
+        ));
+    }
+
+    for (name, content, options) in entries {
+        zip.start_file(name, options).map_err(|e| e.to_string())?;
+        zip.write_all(content.as_bytes())
+            .map_err(|e| e.to_string())?;
+    }
+
+    // The images are already compressed
+    for image in &images {
+        zip.start_file(format!("OEBPS/{}", image.image), stored)
+            .map_err(|e| e.to_string())?;
+        let mut f = File::open(&image.page.path).map_err(|e| e.to_string())?;
+        io::copy(&mut f, &mut zip).map_err(|e| e.to_string())?;
+    }
+
+    zip.finish().map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+/// Returns the images of the pages with their filenames in the EPUB
+///
+/// # Arguments
+///
+/// * `pages` - Pages in reading order
+fn get_images(pages: &[ComicPage]) -> Result<Vec<EpubImage<'_>>, String> {
+    let digit = pages.len().to_string().len().max(4);
+
+    pages
+        .iter()
+        .enumerate()
+        .map(|(i, page)| {
+            let media_type = media_type(&page.path)
+                .ok_or_else(|| format!("{} isn't supported by EPUB", file_name(&page.path)))?;
+            let extension = page
+                .path
+                .extension()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_lowercase();
+            let number = format!("{:0digit$}", i + 1);
+            Ok(EpubImage {
+                page,
+                image: format!("images/{number}.{extension}"),
+                xhtml: format!("text/{number}.xhtml"),
+                media_type,
+            })
+        })
+        .collect()
+}
+
+/// Returns the package document
+///
+/// # Arguments
+///
+/// * `title` - Title of the book
+/// * `images` - Images in reading order
+/// * `direction` - Page progression direction
+fn package_document(title: &str, images: &[EpubImage], direction: ReadingDirection) -> String {
+    let direction = match direction {
+        ReadingDirection::Ltr => "ltr",
+        ReadingDirection::Rtl => "rtl",
+    };
+    let modified = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|v| v.as_secs())
+        .unwrap_or_default();
+
+    let mut opf = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    opf.push_str("<package xmlns=\"http://www.idpf.org/2007/opf\" version=\"3.0\" unique-identifier=\"book-id\" prefix=\"rendition: http://www.idpf.org/vocab/rendition/#\">\n");
+    opf.push_str("  <metadata xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\n");
+    let _ = writeln!(
+        opf,
+        "    <dc:identifier id=\"book-id\">urn:uuid:{}</dc:identifier>",
+        Uuid::new_v4()
+    );
+    let _ = writeln!(opf, "    <dc:title>{}</dc:title>", escape(title));
+    opf.push_str("    <dc:language>und</dc:language>\n");
+    let _ = writeln!(
+        opf,
+        "    <meta property=\"dcterms:modified\">{}</meta>",
+        format_utc(modified)
+    );
+    opf.push_str("    <meta property=\"rendition:layout\">pre-paginated</meta>\n");
+    opf.push_str("    <meta property=\"rendition:orientation\">auto</meta>\n");
+    opf.push_str("    <meta property=\"rendition:spread\">landscape</meta>\n");
+    opf.push_str("    <meta name=\"cover\" content=\"image-1\"/>\n");
+    opf.push_str("  </metadata>\n");
+    opf.push_str("  <manifest>\n");
+    opf.push_str("    <item id=\"nav\" href=\"nav.xhtml\" media-type=\"application/xhtml+xml\" properties=\"nav\"/>\n");
+    for (i, image) in images.iter().enumerate() {
+        let cover = if i == 0 {
+            " properties=\"cover-image\""
+        } else {
+            ""
+        };
+        let _ = writeln!(
+            opf,
+            "    <item id=\"image-{n}\" href=\"{}\" media-type=\"{}\"{cover}/>",
+            image.image,
+            image.media_type,
+            n = i + 1
+        );
+        let _ = writeln!(
+            opf,
+            "    <item id=\"page-{n}\" href=\"{}\" media-type=\"application/xhtml+xml\"/>",
+            image.xhtml,
+            n = i + 1
+        );
+    }
+    opf.push_str("  </manifest>\n");
+    let _ = writeln!(opf, "  <spine page-progression-direction=\"{direction}\">");
+    for i in 0..images.len() {
+        let _ = writeln!(opf, "    <itemref idref=\"page-{}\"/>", i + 1);
+    }
+    opf.push_str("  </spine>\n");
+    opf.push_str("</package>\n");
+
+    opf
+}
+
+/// Returns the navigation document with the cover in the table of contents and every page in the page list
+///
+/// # Arguments
+///
+/// * `title` - Title of the book
+/// * `images` - Images in reading order
+fn navigation_document(title: &str, images: &[EpubImage]) -> String {
+    let mut nav = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    nav.push_str("<!DOCTYPE html>\n");
+    nav.push_str("<html xmlns=\"http://www.w3.org/1999/xhtml\" xmlns:epub=\"http://www.idpf.org/2007/ops\">\n");
+    let _ = writeln!(nav, "<head><title>{}</title></head>", escape(title));
+    nav.push_str("<body>\n");
+    nav.push_str("  <nav epub:type=\"toc\" id=\"toc\">\n    <ol>\n");
+    if let Some(v) = images.first() {
+        let _ = writeln!(nav, "      <li><a href=\"{}\">Cover</a></li>", v.xhtml);
+    }
+    nav.push_str("    </ol>\n  </nav>\n");
+    nav.push_str("  <nav epub:type=\"page-list\" hidden=\"\">\n    <ol>\n");
+    for (i, image) in images.iter().enumerate() {
+        let _ = writeln!(
+            nav,
+            "      <li><a href=\"{}\">{}</a></li>",
+            image.xhtml,
+            i + 1
+        );
+    }
+    nav.push_str("    </ol>\n  </nav>\n");
+    nav.push_str("</body>\n</html>\n");
+
+    nav
+}
+
+/// Returns the XHTML of a page whose viewport is the size of the image
+///
+/// # Arguments
+///
+/// * `title` - Title of the book
+/// * `index` - Index of the page
+/// * `image` - Image of the page
+fn page_document(title: &str, index: usize, image: &EpubImage) -> String {
+    let (width, height) = (image.page.width, image.page.height);
+
+    let mut xhtml = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    xhtml.push_str("<!DOCTYPE html>\n");
+    xhtml.push_str("<html xmlns=\"http://www.w3.org/1999/xhtml\" xmlns:epub=\"http://www.idpf.org/2007/ops\">\n");
+    xhtml.push_str("<head>\n");
+    let _ = writeln!(xhtml, "  <title>{} - {}</title>", escape(title), index + 1);
+    let _ = writeln!(
+        xhtml,
+        "  <meta name=\"viewport\" content=\"width={width}, height={height}\"/>"
+    );
+    xhtml.push_str("  <style>html, body { margin: 0; padding: 0; } img { display: block; width: 100%; height: 100%; }</style>\n");
+    xhtml.push_str("</head>\n");
+    let _ = writeln!(
+        xhtml,
+        "<body><img src=\"../{}\" alt=\"{}\" width=\"{width}\" height=\"{height}\"/></body>",
+        image.image,
+        index + 1
+    );
+    xhtml.push_str("</html>\n");
+
+    xhtml
+}
+
+/// Returns the UTC date time of the given seconds since the UNIX epoch as `YYYY-MM-DDThh:mm:ssZ`
+///
+/// # Arguments
+///
+/// * `secs` - Seconds since the UNIX epoch
+fn format_utc(secs: u64) -> String {
+    let (year, month, day) = civil_date((secs / 86400) as i64);
+    let secs = secs % 86400;
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn page(path: &str) -> ComicPage {
+        ComicPage {
+            path: PathBuf::from(path),
+            width: 800,
+            height: 1200,
+            size: 1024,
+        }
+    }
+
+    #[test]
+    fn package_document_should_have_cover_and_direction() {
+        let pages = [page("a/1.JPG"), page("a/2.png")];
+        let images = get_images(&pages).unwrap();
+        let opf = package_document("a", &images, ReadingDirection::Rtl);
+        assert!(opf.contains("<item id=\"image-1\" href=\"images/0001.jpg\" media-type=\"image/jpeg\" properties=\"cover-image\"/>"));
+        assert!(opf
+            .contains("<item id=\"image-2\" href=\"images/0002.png\" media-type=\"image/png\"/>"));
+        assert!(opf.contains("<spine page-progression-direction=\"rtl\">"));
+    }
+
+    #[test]
+    fn unsupported_image_should_be_error() {
+        let pages = [page("a/1.bmp")];
+        assert!(get_images(&pages).is_err());
+    }
+
+    #[test]
+    fn utc_should_be_formatted() {
+        assert_eq!("1970-01-01T00:00:00Z", format_utc(0));
+        assert_eq!("2024-02-29T12:34:56Z", format_utc(1709210096));
+    }
+}
@@ -0,0 +1,134 @@
+use globset::{Glob, GlobBuilder, GlobSet, GlobSetBuilder};
+use std::path::{Path, PathBuf};
+
+/// Include and exclude glob patterns
+/// A pattern is matched against both the entry name and its path relative to the root directory
+pub struct PathFilter {
+    root: PathBuf,
+    include: Option<GlobSet>,
+    exclude: GlobSet,
+}
+
+impl PathFilter {
+    /// Returns a filter of the given patterns
+    ///
+    /// # Arguments
+    ///
+    /// * `root` - Root directory
+    /// * `include` - Include patterns, everything is included if empty
+    /// * `exclude` - Exclude patterns
+    pub fn new(root: &str, include: &[String], exclude: &[String]) -> Result<Self, String> {
+        Ok(PathFilter {
+            root: PathBuf::from(root),
+            include: if include.is_empty() {
+                None
+            } else {
+                Some(build_glob_set(include)?)
+            },
+            exclude: build_glob_set(exclude)?,
+        })
+    }
+
+    /// Returns true if the given path matches an exclude pattern
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path
+    pub fn is_excluded(&self, path: &Path) -> bool {
+        self.is_match(&self.exclude, path)
+    }
+
+    /// Returns true if the given file or one of its directories under the root matches an include pattern,
+    /// and the file matches no exclude pattern
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path
+    pub fn is_included(&self, path: &Path) -> bool {
+        let included = match &self.include {
+            Some(v) => path
+                .ancestors()
+                .take_while(|v| v.starts_with(&self.root))
+                .any(|p| self.is_match(v, p)),
+            None => true,
+        };
+
+        included && !self.is_excluded(path)
+    }
+
+    fn is_match(&self, set: &GlobSet, path: &Path) -> bool {
+        let relative_path = path.strip_prefix(&self.root).unwrap_or(path);
+        if relative_path.as_os_str().is_empty() {
+            return false;
+        }
+
+        path.file_name().is_some_and(|v| set.is_match(v)) || set.is_match(relative_path)
+    }
+}
+
+/// Returns a glob of the given pattern
+/// `*` does not match the path separator
+///
+/// # Arguments
+///
+/// * `pattern` - Glob pattern
+pub fn build_glob(pattern: &str) -> Result<Glob, String> {
+    GlobBuilder::new(pattern)
+        .literal_separator(true)
+        .build()
+        .map_err(|e| e.to_string())
+}
+
+fn build_glob_set(patterns: &[String]) -> Result<GlobSet, String> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(build_glob(pattern)?);
+    }
+
+    builder.build().map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(include: &[&str], exclude: &[&str]) -> PathFilter {
+        let include = include
+            .iter()
+            .map(|v| v.to_string())
+            .collect::<Vec<String>>();
+        let exclude = exclude
+            .iter()
+            .map(|v| v.to_string())
+            .collect::<Vec<String>>();
+        PathFilter::new("lib", &include, &exclude).unwrap()
+    }
+
+    #[test]
+    fn patterns_should_match_by_name() {
+        let filter = filter(&["*.jpg"], &["cover*"]);
+        assert!(filter.is_included(Path::new("lib/ch1/01.jpg")));
+        assert!(!filter.is_included(Path::new("lib/ch1/01.png")));
+        assert!(!filter.is_included(Path::new("lib/ch1/cover.jpg")));
+        assert!(filter.is_excluded(Path::new("lib/covers")));
+    }
+
+    #[test]
+    fn patterns_should_match_by_relative_path() {
+        let filter = filter(&["ch1/*.jpg"], &["ch1/extra"]);
+        assert!(filter.is_included(Path::new("lib/ch1/01.jpg")));
+        assert!(!filter.is_included(Path::new("lib/ch2/01.jpg")));
+        assert!(filter.is_excluded(Path::new("lib/ch1/extra")));
+        assert!(!filter.is_excluded(Path::new("lib/ch2/extra")));
+    }
+
+    #[test]
+    fn files_in_included_directories_should_be_included() {
+        let filter = filter(&["ch*"], &[]);
+        assert!(filter.is_included(Path::new("lib/ch1/01.jpg")));
+        assert!(filter.is_included(Path::new("lib/ch1/sub/01.jpg")));
+        assert!(!filter.is_included(Path::new("lib/extra/01.jpg")));
+        // The root directory itself doesn't match
+        assert!(!filter.is_included(Path::new("lib/01.jpg")));
+    }
+}
@@ -0,0 +1,488 @@
+use crate::constants::file::INTENT_FILENAME;
+use crate::executor::journal::{self, JournalMove, JournalRun};
+use crate::executor::utils::{file_name, gen_random_path};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::ffi::OsStr;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Intent log written before renaming the files of a directory,
+/// so that an interrupted run can be completed or rolled back
+#[derive(Serialize, Deserialize)]
+pub struct IntentLog {
+    pub run_id: String,
+    pub phase: Phase,
+    pub entries: Vec<IntentEntry>,
+    /// Whether the run undoes the runs of `run_id` in the journal instead of being recorded
+    #[serde(default)]
+    pub undo: bool,
+}
+
+/// Phase of a two-phase rename
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Phase {
+    /// Moving the files to the staged names
+    Staging,
+    /// Moving the staged files to the target names
+    Finishing,
+}
+
+/// A file renamed through a staged temporary name
+#[derive(Clone, Serialize, Deserialize)]
+pub struct IntentEntry {
+    pub original: String,
+    pub staged: String,
+    pub target: String,
+}
+
+/// Result of the recovery of an interrupted run
+pub enum Recovery {
+    /// The staged files were moved back to the original names
+    RolledBack(usize),
+    /// The staged files were moved to the target names
+    Completed(usize),
+    /// The staged files of an undo were moved to the names before the undone run
+    Undone(usize),
+}
+
+/// Returns the intent log filepath of the given directory
+///
+/// # Arguments
+///
+/// * `directory` - Directory
+pub fn intent_path(directory: &Path) -> PathBuf {
+    directory.join(INTENT_FILENAME)
+}
+
+/// Returns the entries to rename the files through staged names
+///
+/// # Arguments
+///
+/// * `directory` - Directory containing the files
+/// * `renames` - Original path and target path of each file
+pub fn plan(directory: &Path, renames: &[(PathBuf, PathBuf)]) -> Result<Vec<IntentEntry>, String> {
+    let mut occupied = fs::read_dir(directory)
+        .map_err(|e| e.to_string())?
+        .filter_map(Result::ok)
+        .map(|v| directory.join(v.file_name()))
+        .collect::<HashSet<PathBuf>>();
+
+    let moving = renames
+        .iter()
+        .filter(|(from, to)| from != to)
+        .collect::<Vec<_>>();
+    let sources = moving
+        .iter()
+        .map(|(from, _)| from)
+        .collect::<HashSet<&PathBuf>>();
+
+    let mut entries = vec![];
+    for (from, to) in moving {
+        if occupied.contains(to) && !sources.contains(to) {
+            return Err(format!(
+                "`{}` already exists and isn't a target file",
+                to.display()
+            ));
+        }
+
+        let extension = from
+            .extension()
+            .unwrap_or_else(|| OsStr::new(""))
+            .to_string_lossy()
+            .to_string();
+        let staged = loop {
+            let v = gen_random_path(directory, &extension);
+            if !occupied.contains(&v) {
+                break v;
+            }
+        };
+        occupied.insert(staged.clone());
+
+        entries.push(IntentEntry {
+            original: file_name(from),
+            staged: file_name(&staged),
+            target: file_name(to),
+        });
+    }
+
+    Ok(entries)
+}
+
+/// Rename the files in two phases
+/// First all files are moved to the staged names, then to the target names
+///
+/// # Arguments
+///
+/// * `directory` - Directory containing the files
+/// * `run_id` - Run ID recorded in the journal
+/// * `entries` - Entries to rename
+/// * `rename_file` - Function to rename a file
+///
+/// # Note
+/// If a file fails to be staged, the staged files are moved back to the original names.
+/// If a file fails to be moved to the target name, the intent log is kept
+/// so that the next run completes the rename.
+pub fn apply<F>(
+    directory: &Path,
+    run_id: &str,
+    entries: Vec<IntentEntry>,
+    rename_file: F,
+) -> Result<(), String>
+where
+    F: FnMut(&Path, &Path) -> io::Result<()>,
+{
+    run(directory, run_id, entries, false, rename_file)
+}
+
+/// Undo the runs of the given run ID in two phases like `apply`
+/// The runs are removed from the journal instead of recording the undo
+///
+/// # Arguments
+///
+/// * `directory` - Directory containing the files
+/// * `run_id` - Run ID to be undone
+/// * `entries` - Entries to rename the files back
+/// * `rename_file` - Function to rename a file
+pub fn undo<F>(
+    directory: &Path,
+    run_id: &str,
+    entries: Vec<IntentEntry>,
+    rename_file: F,
+) -> Result<(), String>
+where
+    F: FnMut(&Path, &Path) -> io::Result<()>,
+{
+    run(directory, run_id, entries, true, rename_file)
+}
+
+/// Returns the intent log of the given directory if exists
+///
+/// # Arguments
+///
+/// * `directory` - Directory
+pub fn read(directory: &Path) -> Result<Option<IntentLog>, String> {
+    let path = intent_path(directory);
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let content = fs::read_to_string(&path).map_err(|e| e.to_string())?;
+    serde_json::from_str(&content)
+        .map(Some)
+        .map_err(|e| format!("Broken intent log {path:?}: {e}"))
+}
+
+/// Rename the files in two phases, recording the run or undoing it in the journal
+///
+/// # Arguments
+///
+/// * `directory` - Directory containing the files
+/// * `run_id` - Run ID
+/// * `entries` - Entries to rename
+/// * `undo` - Whether the run undoes the runs of `run_id`
+/// * `rename_file` - Function to rename a file
+fn run<F>(
+    directory: &Path,
+    run_id: &str,
+    entries: Vec<IntentEntry>,
+    undo: bool,
+    mut rename_file: F,
+) -> Result<(), String>
+where
+    F: FnMut(&Path, &Path) -> io::Result<()>,
+{
+    if entries.is_empty() {
+        return Ok(());
+    }
+
+    let mut log = IntentLog {
+        run_id: run_id.to_string(),
+        phase: Phase::Staging,
+        entries,
+        undo,
+    };
+    write(directory, &log)?;
+
+    for (i, v) in log.entries.iter().enumerate() {
+        if let Err(e) = rename_file(&directory.join(&v.original), &directory.join(&v.staged)) {
+            let error = format!("Renaming `{}` to `{}`: {e}", v.original, v.staged);
+            for v in log.entries[..i].iter().rev() {
+                if let Err(e) = fs::rename(directory.join(&v.staged), directory.join(&v.original)) {
+                    return Err(format!(
+                        "{error}\nFailed to roll back `{}` to `{}`: {e}\nThe next run will retry the rollback",
+                        v.staged, v.original
+                    ));
+                }
+            }
+            fs::remove_file(intent_path(directory)).map_err(|e| e.to_string())?;
+            return Err(error);
+        }
+    }
+
+    log.phase = Phase::Finishing;
+    write(directory, &log)?;
+
+    for v in &log.entries {
+        rename_file(&directory.join(&v.staged), &directory.join(&v.target)).map_err(|e| {
+            format!(
+                "Renaming `{}` to `{}`: {e}\nThe next run will complete the rename",
+                v.staged, v.target
+            )
+        })?;
+    }
+
+    finish(directory, &log)
+}
+
+/// Complete or roll back the interrupted run of the given directory if exists
+///
+/// # Arguments
+///
+/// * `directory` - Directory
+pub fn recover(directory: &Path) -> Result<Option<Recovery>, String> {
+    let Some(log) = read(directory)? else {
+        return Ok(None);
+    };
+
+    let mut count = 0;
+    for v in &log.entries {
+        let staged = directory.join(&v.staged);
+        if !staged.exists() {
+            continue;
+        }
+
+        let to = match log.phase {
+            Phase::Staging => &v.original,
+            Phase::Finishing => &v.target,
+        };
+        let to = directory.join(to);
+        if to.exists() {
+            return Err(format!(
+                "Can't move `{}` back to `{}` since it already exists",
+                v.staged,
+                to.display()
+            ));
+        }
+        fs::rename(&staged, &to).map_err(|e| format!("Renaming `{}`: {e}", v.staged))?;
+        count += 1;
+    }
+
+    match log.phase {
+        Phase::Staging => {
+            fs::remove_file(intent_path(directory)).map_err(|e| e.to_string())?;
+            Ok(Some(Recovery::RolledBack(count)))
+        }
+        Phase::Finishing if log.undo => {
+            finish(directory, &log)?;
+            Ok(Some(Recovery::Undone(count)))
+        }
+        Phase::Finishing => {
+            finish(directory, &log)?;
+            Ok(Some(Recovery::Completed(count)))
+        }
+    }
+}
+
+/// Record the completed run in the journal and remove the intent log
+/// The completed undo removes the undone runs from the journal instead
+///
+/// # Arguments
+///
+/// * `directory` - Directory
+/// * `log` - Intent log of the completed run
+fn finish(directory: &Path, log: &IntentLog) -> Result<(), String> {
+    if log.undo {
+        journal::remove_last_runs(directory, &log.run_id)?;
+        return fs::remove_file(intent_path(directory)).map_err(|e| e.to_string());
+    }
+
+    let mut moves = log
+        .entries
+        .iter()
+        .map(|v| JournalMove::new(&directory.join(&v.original), &directory.join(&v.staged)))
+        .collect::<Vec<JournalMove>>();
+    moves.extend(
+        log.entries
+            .iter()
+            .map(|v| JournalMove::new(&directory.join(&v.staged), &directory.join(&v.target))),
+    );
+
+    // The run may have been recorded right before the interruption
+    let recorded = journal::read(directory)?.runs.last().is_some_and(|v| {
+        v.id == log.run_id
+            && v.moves.len() == moves.len()
+            && v.moves
+                .iter()
+                .zip(&moves)
+                .all(|(a, b)| a.from == b.from && a.to == b.to)
+    });
+
+    if !recorded {
+        journal::append_run(
+            directory,
+            JournalRun {
+                id: log.run_id.clone(),
+                time: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .ok()
+                    .map(|v| v.as_nanos() as u64),
+                moves,
+            },
+        )?;
+    }
+
+    fs::remove_file(intent_path(directory)).map_err(|e| e.to_string())
+}
+
+/// Write the intent log atomically
+///
+/// # Arguments
+///
+/// * `directory` - Directory
+/// * `log` - Intent log
+fn write(directory: &Path, log: &IntentLog) -> Result<(), String> {
+    let path = intent_path(directory);
+    let temporary_path = path.with_extension("json.tmp");
+    let content = serde_json::to_string_pretty(log).map_err(|e| e.to_string())?;
+
+    File::create(&temporary_path)
+        .and_then(|mut file| {
+            file.write_all(content.as_bytes())?;
+            file.sync_all()
+        })
+        .and_then(|_| fs::rename(&temporary_path, &path))
+        .map_err(|e| format!("Writing intent log {path:?}: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn touch(directory: &Path, names: &[&str]) {
+        for name in names {
+            fs::write(directory.join(name), name).unwrap();
+        }
+    }
+
+    fn names(directory: &Path) -> Vec<String> {
+        let mut names = fs::read_dir(directory)
+            .unwrap()
+            .map(|v| v.unwrap().file_name().to_string_lossy().to_string())
+            .collect::<Vec<String>>();
+        names.sort();
+        names
+    }
+
+    fn entry(original: &str, staged: &str, target: &str) -> IntentEntry {
+        IntentEntry {
+            original: original.to_string(),
+            staged: staged.to_string(),
+            target: target.to_string(),
+        }
+    }
+
+    fn log(phase: Phase) -> IntentLog {
+        IntentLog {
+            run_id: "run".to_string(),
+            phase,
+            entries: vec![
+                entry("a.jpg", "tmp-a.jpg", "b.jpg"),
+                entry("b.jpg", "tmp-b.jpg", "a.jpg"),
+            ],
+            undo: false,
+        }
+    }
+
+    #[test]
+    fn recovery_in_staging_should_roll_back() {
+        let dir = TempDir::new().unwrap();
+        // Interrupted after staging a.jpg
+        touch(dir.path(), &["tmp-a.jpg", "b.jpg"]);
+        write(dir.path(), &log(Phase::Staging)).unwrap();
+
+        let recovery = recover(dir.path()).unwrap();
+        assert!(matches!(recovery, Some(Recovery::RolledBack(1))));
+        assert_eq!(vec!["a.jpg", "b.jpg"], names(dir.path()));
+        assert_eq!(
+            "tmp-a.jpg",
+            fs::read_to_string(dir.path().join("a.jpg")).unwrap()
+        );
+        assert!(journal::read(dir.path()).unwrap().runs.is_empty());
+    }
+
+    #[test]
+    fn recovery_in_finishing_should_complete_and_journal_once() {
+        let dir = TempDir::new().unwrap();
+        // Interrupted after moving tmp-a.jpg to b.jpg
+        touch(dir.path(), &["b.jpg", "tmp-b.jpg"]);
+        write(dir.path(), &log(Phase::Finishing)).unwrap();
+
+        let recovery = recover(dir.path()).unwrap();
+        assert!(matches!(recovery, Some(Recovery::Completed(1))));
+        assert_eq!(
+            vec![".rimg-journal.json", "a.jpg", "b.jpg"],
+            names(dir.path())
+        );
+        assert_eq!(1, journal::read(dir.path()).unwrap().runs.len());
+
+        // Interrupted after the run was recorded but before the intent log was removed
+        write(dir.path(), &log(Phase::Finishing)).unwrap();
+        let recovery = recover(dir.path()).unwrap();
+        assert!(matches!(recovery, Some(Recovery::Completed(0))));
+        assert_eq!(1, journal::read(dir.path()).unwrap().runs.len());
+        assert!(!intent_path(dir.path()).exists());
+    }
+
+    #[test]
+    fn staging_failure_should_roll_back_and_remove_intent_log() {
+        let dir = TempDir::new().unwrap();
+        touch(dir.path(), &["a.jpg", "b.jpg"]);
+
+        let mut calls = 0;
+        let result = apply(
+            dir.path(),
+            "run",
+            log(Phase::Staging).entries,
+            |from, to| {
+                calls += 1;
+                if calls == 2 {
+                    return Err(io::Error::other("failure"));
+                }
+                fs::rename(from, to)
+            },
+        );
+
+        assert!(result.is_err());
+        assert_eq!(vec!["a.jpg", "b.jpg"], names(dir.path()));
+        assert_eq!(
+            "a.jpg",
+            fs::read_to_string(dir.path().join("a.jpg")).unwrap()
+        );
+    }
+
+    #[test]
+    fn plan_should_refuse_target_which_is_not_target_file() {
+        let dir = TempDir::new().unwrap();
+        touch(dir.path(), &["a.jpg", "b.jpg", "0001.jpg"]);
+        let path = |v: &str| dir.path().join(v);
+
+        let result = plan(dir.path(), &[(path("a.jpg"), path("0001.jpg"))]);
+        assert!(result.is_err());
+
+        // A target which is renamed by itself is fine
+        let entries = plan(
+            dir.path(),
+            &[
+                (path("a.jpg"), path("0001.jpg")),
+                (path("0001.jpg"), path("0002.jpg")),
+            ],
+        )
+        .unwrap();
+        assert_eq!(2, entries.len());
+    }
+}
@@ -0,0 +1,223 @@
+use crate::{
+    executor::sort::sort_natural,
+    executor::utils::{ask, file_name, get_files, get_progress_bar},
+    params::interleave::InterleaveParams,
+};
+use colored::Colorize;
+use std::{
+    ffi::OsStr,
+    fs,
+    path::{Path, PathBuf},
+    process,
+};
+
+/// Merge the front pages and the back pages into one alternating sequence
+///
+/// # Arguments
+///
+/// * `params` - Interleave params
+pub fn execute(params: &InterleaveParams) {
+    let fronts = get_target_files(Path::new(&params.fronts), params);
+    let mut backs = get_target_files(Path::new(&params.backs), params);
+    if params.reverse_backs {
+        backs.reverse();
+    }
+
+    if fronts.is_empty() {
+        eprintln!(
+            "{}",
+            format!(
+                "There are no {} files in {} directory",
+                params.extensions.join(", ").to_lowercase(),
+                file_name(Path::new(&params.fronts))
+            )
+            .red()
+            .bold()
+        );
+        eprintln!("Abort...");
+        process::exit(0);
+    }
+
+    // The last sheet may have no back page
+    if backs.len() != fronts.len() && backs.len() + 1 != fronts.len() {
+        eprintln!(
+            "{}",
+            format!(
+                "There are {} front pages but {} back pages",
+                fronts.len(),
+                backs.len()
+            )
+            .red()
+            .bold()
+        );
+        eprintln!("Abort...");
+        process::exit(1);
+    }
+
+    let output_dir = Path::new(&params.output_dir);
+    let pages = interleave(&fronts, &backs, output_dir, params);
+
+    if let Some((_, to_path)) = pages.iter().find(|(_, v)| v.exists()) {
+        eprintln!(
+            "{}",
+            format!("`{}` already exists", to_path.display())
+                .red()
+                .bold()
+        );
+        eprintln!("Abort...");
+        process::exit(1);
+    }
+
+    if params.dry_run {
+        println!("Interleave plan for {} directory", file_name(output_dir));
+        let width = pages
+            .iter()
+            .map(|(v, _)| v.display().to_string().chars().count())
+            .max()
+            .unwrap_or(0);
+        for (from_path, to_path) in &pages {
+            println!(
+                "  {:width$}  ->  {}",
+                from_path.display().to_string(),
+                file_name(to_path)
+            );
+        }
+        return;
+    }
+
+    println!("{} pages will be interleaved", pages.len());
+
+    ask(params.yes);
+
+    if let Err(e) = fs::create_dir_all(output_dir) {
+        eprintln!(
+            "{}",
+            format!("Failed to create {} directory", output_dir.display())
+                .red()
+                .bold()
+        );
+        eprintln!("{e}");
+        process::exit(1);
+    }
+
+    let bar = get_progress_bar(pages.len() as u64);
+    bar.set_message("Interleaving");
+    for (from_path, to_path) in &pages {
+        let result = if params.r#move {
+            fs::rename(from_path, to_path)
+        } else {
+            fs::copy(from_path, to_path).map(|_| ())
+        };
+
+        if let Err(e) = result {
+            bar.abandon();
+            eprintln!(
+                "{}",
+                format!(
+                    "Failed to put `{}` as `{}`",
+                    from_path.display(),
+                    to_path.display()
+                )
+                .red()
+                .bold()
+            );
+            eprintln!("{e}");
+            process::exit(1);
+        }
+        bar.inc(1);
+    }
+    bar.set_message("Interleave complete");
+    bar.finish();
+
+    println!(
+        "{}",
+        format!(
+            "Interleaved {} fronts and {} backs into {} directory",
+            fronts.len(),
+            backs.len(),
+            file_name(output_dir)
+        )
+        .green()
+        .bold()
+    );
+}
+
+/// Returns the target files directly under the given directory in natural order
+///
+/// # Arguments
+///
+/// * `directory` - Directory
+/// * `params` - Interleave params
+fn get_target_files(directory: &Path, params: &InterleaveParams) -> Vec<PathBuf> {
+    let mut files = get_files(directory, &params.extensions, |_| true);
+
+    sort_natural(&mut files);
+
+    files
+}
+
+/// Returns the original path and the output path of each page,
+/// alternating the front pages and the back pages
+///
+/// # Arguments
+///
+/// * `fronts` - Front pages
+/// * `backs` - Back pages
+/// * `output_dir` - Output directory
+/// * `params` - Interleave params
+fn interleave(
+    fronts: &[PathBuf],
+    backs: &[PathBuf],
+    output_dir: &Path,
+    params: &InterleaveParams,
+) -> Vec<(PathBuf, PathBuf)> {
+    let mut pages = vec![];
+    for (i, front) in fronts.iter().enumerate() {
+        pages.push(front);
+        if let Some(back) = backs.get(i) {
+            pages.push(back);
+        }
+    }
+
+    pages
+        .into_iter()
+        .enumerate()
+        .map(|(i, from_path)| {
+            let number = params.initial + i as u32 * params.step as u32;
+            let extension = from_path
+                .extension()
+                .unwrap_or_else(|| OsStr::new(""))
+                .to_string_lossy();
+            let to_path = output_dir.join(format!(
+                "{number:0width$}.{extension}",
+                width = params.digit as usize
+            ));
+            (from_path.clone(), to_path)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[test]
+    fn pages_should_alternate_fronts_and_backs() {
+        let params = InterleaveParams::parse_from(["interleave", ".", ".", "-o", "out", "-d", "3"]);
+        let fronts = [PathBuf::from("f/a1.jpg"), PathBuf::from("f/a2.jpg")];
+        let backs = [PathBuf::from("b/b1.png")];
+        let pages = interleave(&fronts, &backs, Path::new("out"), &params)
+            .into_iter()
+            .map(|(from, to)| (from.to_string_lossy().to_string(), file_name(&to)))
+            .collect::<Vec<(String, String)>>();
+        assert_eq!(
+            vec![
+                ("f/a1.jpg".to_string(), "001.jpg".to_string()),
+                ("b/b1.png".to_string(), "002.png".to_string()),
+                ("f/a2.jpg".to_string(), "003.jpg".to_string()),
+            ],
+            pages
+        );
+    }
+}
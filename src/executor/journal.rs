@@ -0,0 +1,186 @@
+use crate::constants::file::JOURNAL_FILENAME;
+use crate::executor::utils::file_name;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Rename journal stored in each renamed directory
+#[derive(Default, Serialize, Deserialize)]
+pub struct Journal {
+    pub runs: Vec<JournalRun>,
+}
+
+/// Moves done by a single rename run
+#[derive(Serialize, Deserialize)]
+pub struct JournalRun {
+    pub id: String,
+    /// Finished time in nanoseconds since the unix epoch, to find the last run across the directories
+    pub time: Option<u64>,
+    pub moves: Vec<JournalMove>,
+}
+
+/// A single move, including temporary moves to avoid collisions
+#[derive(Clone, Serialize, Deserialize)]
+pub struct JournalMove {
+    /// Filename before the move
+    pub from: String,
+    /// Filename after the move
+    pub to: String,
+    /// File size after the move
+    pub len: Option<u64>,
+    /// Modified time after the move in nanoseconds since the unix epoch
+    pub modified: Option<u64>,
+}
+
+impl JournalMove {
+    /// Returns a move record with the current metadata of the destination
+    ///
+    /// # Arguments
+    ///
+    /// * `from_path` - From path
+    /// * `to_path` - To path
+    pub fn new(from_path: &Path, to_path: &Path) -> Self {
+        let (len, modified) = file_stamp(to_path);
+        JournalMove {
+            from: file_name(from_path),
+            to: file_name(to_path),
+            len,
+            modified,
+        }
+    }
+}
+
+/// Returns the journal filepath of the given directory
+///
+/// # Arguments
+///
+/// * `directory` - Directory
+pub fn journal_path(directory: &Path) -> PathBuf {
+    directory.join(JOURNAL_FILENAME)
+}
+
+/// Read the journal of the given directory
+/// Returns an empty journal if the directory has no journal
+///
+/// # Arguments
+///
+/// * `directory` - Directory
+pub fn read(directory: &Path) -> Result<Journal, String> {
+    let path = journal_path(directory);
+    if !path.exists() {
+        return Ok(Journal::default());
+    }
+
+    let content = fs::read_to_string(&path).map_err(|e| e.to_string())?;
+    serde_json::from_str(&content).map_err(|e| format!("Broken journal {path:?}: {e}"))
+}
+
+/// Write the journal of the given directory
+/// The journal file is removed if there are no runs
+///
+/// # Arguments
+///
+/// * `directory` - Directory
+/// * `journal` - Journal
+pub fn write(directory: &Path, journal: &Journal) -> Result<(), String> {
+    let path = journal_path(directory);
+    if journal.runs.is_empty() {
+        if path.exists() {
+            fs::remove_file(&path).map_err(|e| e.to_string())?;
+        }
+        return Ok(());
+    }
+
+    let content = serde_json::to_string_pretty(journal).map_err(|e| e.to_string())?;
+    fs::write(&path, content).map_err(|e| e.to_string())
+}
+
+/// Append a run to the journal of the given directory
+///
+/// # Arguments
+///
+/// * `directory` - Directory
+/// * `run` - Run to append
+pub fn append_run(directory: &Path, run: JournalRun) -> Result<(), String> {
+    if run.moves.is_empty() {
+        return Ok(());
+    }
+
+    let mut journal = read(directory)?;
+    journal.runs.push(run);
+    write(directory, &journal)
+}
+
+/// Remove the last runs of the given run ID from the journal of the given directory
+///
+/// # Arguments
+///
+/// * `directory` - Directory
+/// * `run_id` - Run ID
+pub fn remove_last_runs(directory: &Path, run_id: &str) -> Result<(), String> {
+    let mut journal = read(directory)?;
+    while journal.runs.last().is_some_and(|v| v.id == run_id) {
+        journal.runs.pop();
+    }
+    write(directory, &journal)
+}
+
+/// Check the files of the run were not changed since the run
+/// and the original filenames are still available
+///
+/// # Arguments
+///
+/// * `directory` - Directory
+/// * `run` - Run to check
+pub fn check_undoable(directory: &Path, run: &JournalRun) -> Result<(), String> {
+    // Final location of each file after the run and its metadata
+    let mut present = HashMap::<String, (Option<u64>, Option<u64>)>::new();
+    for v in &run.moves {
+        present.remove(&v.from);
+        present.insert(v.to.clone(), (v.len, v.modified));
+    }
+
+    for (name, (len, modified)) in &present {
+        let path = directory.join(name);
+        if !path.exists() {
+            return Err(format!("`{name}` no longer exists"));
+        }
+
+        let (current_len, current_modified) = file_stamp(&path);
+        if current_len != *len || current_modified != *modified {
+            return Err(format!("`{name}` was changed since it was renamed"));
+        }
+    }
+
+    // The other filenames used by the run must be free to replay it backwards
+    for v in &run.moves {
+        for name in [&v.from, &v.to] {
+            if !present.contains_key(name) && directory.join(name).exists() {
+                return Err(format!("`{name}` already exists"));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the size and the modified time of the given file
+///
+/// # Arguments
+///
+/// * `path` - Path
+fn file_stamp(path: &Path) -> (Option<u64>, Option<u64>) {
+    match fs::metadata(path) {
+        Ok(v) if v.is_file() => {
+            let modified = v
+                .modified()
+                .ok()
+                .and_then(|v| v.duration_since(UNIX_EPOCH).ok())
+                .map(|v| v.as_nanos() as u64);
+            (Some(v.len()), modified)
+        }
+        _ => (None, None),
+    }
+}
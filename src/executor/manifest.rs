@@ -0,0 +1,128 @@
+use crate::constants::file::{MANIFEST_CSV_FILENAME, MANIFEST_JSON_FILENAME};
+use crate::executor::utils::file_name;
+use crate::params::rename::ManifestFormat;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// Original name and new name of a renamed file
+/// Size and hash are of the file content, which are optional in a hand-edited manifest
+#[derive(Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub original: String,
+    pub new: String,
+    pub size: Option<u64>,
+    pub sha256: Option<String>,
+}
+
+impl ManifestEntry {
+    /// Returns the entry of a renamed file with the size and hash of its content
+    ///
+    /// # Arguments
+    ///
+    /// * `original` - Original path
+    /// * `new` - Current path of the file
+    pub fn new(original: &Path, new: &Path) -> Result<Self, String> {
+        let size = fs::metadata(new)
+            .map_err(|e| format!("Reading {new:?}: {e}"))?
+            .len();
+
+        Ok(ManifestEntry {
+            original: file_name(original),
+            new: file_name(new),
+            size: Some(size),
+            sha256: Some(sha256(new)?),
+        })
+    }
+}
+
+/// Returns the manifest filepath of the given directory
+///
+/// # Arguments
+///
+/// * `directory` - Directory
+/// * `format` - Manifest format
+pub fn manifest_path(directory: &Path, format: ManifestFormat) -> PathBuf {
+    directory.join(match format {
+        ManifestFormat::Csv => MANIFEST_CSV_FILENAME,
+        ManifestFormat::Json => MANIFEST_JSON_FILENAME,
+    })
+}
+
+/// Write the manifest of the given directory
+///
+/// # Arguments
+///
+/// * `directory` - Directory
+/// * `format` - Manifest format
+/// * `entries` - Manifest entries
+pub fn write(
+    directory: &Path,
+    format: ManifestFormat,
+    entries: &[ManifestEntry],
+) -> Result<(), String> {
+    let path = manifest_path(directory, format);
+    let content = match format {
+        ManifestFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(vec![]);
+            for entry in entries {
+                writer.serialize(entry).map_err(|e| e.to_string())?;
+            }
+            writer.into_inner().map_err(|e| e.to_string())?
+        }
+        ManifestFormat::Json => serde_json::to_vec_pretty(entries).map_err(|e| e.to_string())?,
+    };
+
+    fs::write(&path, content).map_err(|e| format!("Writing manifest {path:?}: {e}"))
+}
+
+/// Read the manifest of the given path
+/// The format is JSON if the extension is `json`, otherwise CSV
+///
+/// # Arguments
+///
+/// * `path` - Manifest path
+pub fn read(path: &Path) -> Result<Vec<ManifestEntry>, String> {
+    let content = fs::read(path).map_err(|e| format!("Reading manifest {path:?}: {e}"))?;
+
+    if path
+        .extension()
+        .is_some_and(|v| v.eq_ignore_ascii_case("json"))
+    {
+        return serde_json::from_slice(&content)
+            .map_err(|e| format!("Broken manifest {path:?}: {e}"));
+    }
+
+    csv::Reader::from_reader(content.as_slice())
+        .deserialize()
+        .collect::<Result<Vec<ManifestEntry>, csv::Error>>()
+        .map_err(|e| format!("Broken manifest {path:?}: {e}"))
+}
+
+/// Returns the SHA-256 hash of the file content as lowercase hex
+///
+/// # Arguments
+///
+/// * `path` - File
+pub fn sha256(path: &Path) -> Result<String, String> {
+    let mut file = File::open(path).map_err(|e| format!("Reading {path:?}: {e}"))?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 1 << 16];
+    loop {
+        let len = file
+            .read(&mut buffer)
+            .map_err(|e| format!("Reading {path:?}: {e}"))?;
+        if len == 0 {
+            break;
+        }
+        hasher.update(&buffer[..len]);
+    }
+
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|v| format!("{v:02x}"))
+        .collect())
+}
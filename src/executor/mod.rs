@@ -1,3 +1,16 @@
+pub mod check_sequence;
+mod comic_info;
 pub mod compress;
+mod epub;
+pub mod filter;
+mod intent;
+pub mod interleave;
+mod journal;
+mod manifest;
+pub mod pattern;
+mod pdf;
 pub mod rename;
+mod sort;
+mod spread;
+pub mod undo;
 mod utils;
@@ -0,0 +1,331 @@
+use crate::executor::utils::civil_date;
+use regex::Captures;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Pattern used when no pattern was specified
+pub const DEFAULT_PATTERN: &str = "{n}.{ext}";
+
+/// Filename template such as `{dir}_p{n:04}.{ext}`
+pub struct Pattern {
+    segments: Vec<Segment>,
+}
+
+enum Segment {
+    Literal(String),
+    /// Name of the directory containing the file
+    Dir,
+    /// Name of the parent of the directory containing the file
+    Parent,
+    /// Sequential number padded with the given width, or `--digit` if not given
+    /// Spreads are given both numbers such as `0012-0013`
+    Number(Option<usize>),
+    /// Original filename without extension
+    Orig,
+    /// Original extension
+    Ext,
+    /// Modified date of the file as `YYYYMMDD`
+    Mtime,
+    /// Capture group of `--regex` by index or name,
+    /// padded with the given width if the captured text is a number
+    Capture(String, Option<usize>),
+}
+
+/// Values to fill the placeholders of a pattern
+pub struct PatternContext<'a> {
+    pub dir: &'a str,
+    pub parent: &'a str,
+    pub number: u32,
+    /// Last number of a spread numbered as the range of pages
+    pub number_end: Option<u32>,
+    pub digit: usize,
+    pub orig: &'a str,
+    pub ext: &'a str,
+    pub mtime: Option<SystemTime>,
+    pub captures: Option<&'a Captures<'a>>,
+}
+
+impl Pattern {
+    /// Parse the given template
+    ///
+    /// # Arguments
+    ///
+    /// * `s` - Template
+    pub fn parse(s: &str) -> Result<Pattern, String> {
+        Self::parse_template(s, false)
+    }
+
+    /// Parse the given template which may refer to the capture groups of `--regex`
+    /// such as `{1}`, `{1:04}` or `{page}`
+    ///
+    /// # Arguments
+    ///
+    /// * `s` - Template
+    pub fn parse_with_captures(s: &str) -> Result<Pattern, String> {
+        Self::parse_template(s, true)
+    }
+
+    /// Returns the names of the capture groups used in the pattern
+    pub fn capture_names(&self) -> Vec<&str> {
+        self.segments
+            .iter()
+            .filter_map(|v| match v {
+                Segment::Capture(name, _) => Some(name.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn parse_template(s: &str, allow_captures: bool) -> Result<Pattern, String> {
+        let mut segments = vec![];
+        let mut literal = String::new();
+        let mut chars = s.chars();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('{') if name.is_empty() => {
+                                literal.push('{');
+                                break;
+                            }
+                            Some('}') => {
+                                if !literal.is_empty() {
+                                    segments.push(Segment::Literal(literal.clone()));
+                                    literal.clear();
+                                }
+                                segments.push(parse_placeholder(&name, allow_captures)?);
+                                break;
+                            }
+                            Some(v) => name.push(v),
+                            None => return Err(format!("Unclosed placeholder `{{{name}`")),
+                        }
+                    }
+                }
+                '}' => {
+                    if chars.next() != Some('}') {
+                        return Err("Unmatched `}`, use `}}` for a literal `}`".to_string());
+                    }
+                    literal.push('}');
+                }
+                '/' | '\\' => return Err(format!("`{c}` can't be used in filename")),
+                _ => literal.push(c),
+            }
+        }
+
+        if !literal.is_empty() {
+            segments.push(Segment::Literal(literal));
+        }
+
+        if !segments
+            .iter()
+            .any(|v| matches!(v, Segment::Number(_) | Segment::Orig | Segment::Capture(..)))
+        {
+            return Err(if allow_captures {
+                "Template must contain `{n}`, `{orig}` or a capture group to make unique filenames"
+            } else {
+                "Pattern must contain `{n}` or `{orig}` to make unique filenames"
+            }
+            .to_string());
+        }
+
+        Ok(Pattern { segments })
+    }
+
+    /// Returns the filename filled with the given values
+    ///
+    /// # Arguments
+    ///
+    /// * `context` - Values for the placeholders
+    pub fn render(&self, context: &PatternContext) -> String {
+        let mut s = String::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(v) => s.push_str(v),
+                Segment::Dir => s.push_str(context.dir),
+                Segment::Parent => s.push_str(context.parent),
+                Segment::Number(width) => {
+                    let width = width.unwrap_or(context.digit);
+                    s.push_str(&format!("{:0width$}", context.number));
+                    if let Some(v) = context.number_end {
+                        s.push_str(&format!("-{v:0width$}"));
+                    }
+                }
+                Segment::Orig => s.push_str(context.orig),
+                Segment::Ext => s.push_str(context.ext),
+                Segment::Mtime => {
+                    if let Some(v) = context.mtime {
+                        s.push_str(&format_date(v));
+                    }
+                }
+                Segment::Capture(name, width) => {
+                    let captured = context
+                        .captures
+                        .and_then(|v| match name.parse::<usize>() {
+                            Ok(i) => v.get(i),
+                            Err(_) => v.name(name),
+                        })
+                        .map(|v| v.as_str())
+                        .unwrap_or_default();
+
+                    match width {
+                        Some(width) if captured.chars().all(|c| c.is_ascii_digit()) => {
+                            let digits = captured.trim_start_matches('0');
+                            let digits = if digits.is_empty() { "0" } else { digits };
+                            s.push_str(&format!("{digits:0>width$}"));
+                        }
+                        _ => s.push_str(captured),
+                    }
+                }
+            }
+        }
+
+        s
+    }
+}
+
+/// Returns the segment of the given placeholder name
+///
+/// # Arguments
+///
+/// * `name` - Placeholder name without braces
+/// * `allow_captures` - Treat the other names as capture groups
+fn parse_placeholder(name: &str, allow_captures: bool) -> Result<Segment, String> {
+    match name {
+        "dir" => Ok(Segment::Dir),
+        "parent" => Ok(Segment::Parent),
+        "n" => Ok(Segment::Number(None)),
+        "orig" => Ok(Segment::Orig),
+        "ext" => Ok(Segment::Ext),
+        "mtime" => Ok(Segment::Mtime),
+        _ => match name.strip_prefix("n:") {
+            Some(v) if !v.is_empty() && v.len() <= 2 && v.chars().all(|c| c.is_ascii_digit()) => {
+                Ok(Segment::Number(Some(v.parse::<usize>().unwrap())))
+            }
+            _ if allow_captures => parse_capture(name),
+            _ => Err(format!(
+                "`{{{name}}}` isn't supported placeholder\nCurrently supports `{{dir}}`, `{{parent}}`, `{{n}}`, `{{n:04}}`, `{{orig}}`, `{{ext}}` and `{{mtime}}`"
+            )),
+        },
+    }
+}
+
+/// Returns the capture group segment of the given placeholder name such as `1`, `page` or `page:04`
+///
+/// # Arguments
+///
+/// * `name` - Placeholder name without braces
+fn parse_capture(name: &str) -> Result<Segment, String> {
+    let (group, width) = match name.split_once(':') {
+        Some((group, width)) => {
+            if width.is_empty() || width.len() > 2 || !width.chars().all(|c| c.is_ascii_digit()) {
+                return Err(format!("`{{{name}}}` has invalid width"));
+            }
+            (group, Some(width.parse::<usize>().unwrap()))
+        }
+        None => (name, None),
+    };
+
+    if group.is_empty() || !group.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err(format!(
+            "`{{{name}}}` isn't supported placeholder nor capture group"
+        ));
+    }
+
+    Ok(Segment::Capture(group.to_string(), width))
+}
+
+/// Returns the given time as `YYYYMMDD` in UTC
+///
+/// # Arguments
+///
+/// * `time` - Time
+fn format_date(time: SystemTime) -> String {
+    let days = time
+        .duration_since(UNIX_EPOCH)
+        .map(|v| v.as_secs() / 86400)
+        .unwrap_or(0) as i64;
+
+    let (year, month, day) = civil_date(days);
+
+    format!("{year:04}{month:02}{day:02}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn context(number: u32) -> PatternContext<'static> {
+        PatternContext {
+            dir: "v03",
+            parent: "MySeries",
+            number,
+            number_end: None,
+            digit: 4,
+            orig: "IMG_0001",
+            ext: "jpg",
+            mtime: Some(UNIX_EPOCH + Duration::from_secs(1_700_000_000)),
+            captures: None,
+        }
+    }
+
+    #[test]
+    fn default_pattern_should_be_zero_padded_number() {
+        let pattern = Pattern::parse(DEFAULT_PATTERN).unwrap();
+        assert_eq!("0012.jpg", pattern.render(&context(12)));
+    }
+
+    #[test]
+    fn placeholders_should_be_filled() {
+        let pattern = Pattern::parse("{parent}_{dir}_p{n:03}_{orig}_{mtime}.{ext}").unwrap();
+        assert_eq!(
+            "MySeries_v03_p012_IMG_0001_20231114.jpg",
+            pattern.render(&context(12))
+        );
+    }
+
+    #[test]
+    fn escaped_braces_should_be_literal() {
+        let pattern = Pattern::parse("{{{n}}}.{ext}").unwrap();
+        assert_eq!("{0001}.jpg", pattern.render(&context(1)));
+    }
+
+    #[test]
+    fn unknown_placeholder_should_be_error() {
+        assert!(Pattern::parse("{foo}_{n}").is_err());
+    }
+
+    #[test]
+    fn pattern_without_unique_part_should_be_error() {
+        assert!(Pattern::parse("{dir}.{ext}").is_err());
+    }
+
+    #[test]
+    fn spread_should_have_both_numbers() {
+        let pattern = Pattern::parse("{dir}_p{n:03}.{ext}").unwrap();
+        let context = PatternContext {
+            number_end: Some(13),
+            ..context(12)
+        };
+        assert_eq!("v03_p012-013.jpg", pattern.render(&context));
+    }
+
+    #[test]
+    fn capture_groups_should_be_filled_and_padded() {
+        let regex = regex::Regex::new(r"_(?P<year>\d+)_\d+_p(\d+)$").unwrap();
+        let captures = regex.captures("IMG_2023_0045_p12").unwrap();
+        let pattern = Pattern::parse_with_captures("{year}_p{2:04}.{ext}").unwrap();
+        let context = PatternContext {
+            captures: Some(&captures),
+            ..context(1)
+        };
+        assert_eq!("2023_p0012.jpg", pattern.render(&context));
+    }
+
+    #[test]
+    fn capture_groups_should_be_error_without_regex() {
+        assert!(Pattern::parse("{1}.{ext}").is_err());
+    }
+}
@@ -0,0 +1,301 @@
+use crate::executor::comic_info::ComicPage;
+use crate::executor::utils::file_name;
+use flate2::{write::ZlibEncoder, Compression};
+use lopdf::{dictionary, Document, Object, Stream};
+use std::fs::{self, File};
+use std::io::{BufReader, Write};
+use std::path::Path;
+
+/// Returns true if the given image can be a page of PDF
+///
+/// # Arguments
+///
+/// * `path` - Image path
+pub fn is_supported(path: &Path) -> bool {
+    path.extension()
+        .map(|v| v.to_string_lossy().to_lowercase())
+        .is_some_and(|v| ["jpg", "jpeg", "png"].contains(&v.as_str()))
+}
+
+/// Write a PDF with one page per image at its native size
+/// JPEG is embedded as it is, and PNG is embedded losslessly with its alpha as a soft mask
+///
+/// # Arguments
+///
+/// * `pages` - Pages in reading order, whose images are JPEG or PNG
+/// * `output_filepath` - Output filepath
+pub fn write(pages: &[ComicPage], output_filepath: &Path) -> Result<(), String> {
+    let mut doc = Document::with_version("1.5");
+    let pages_id = doc.new_object_id();
+
+    let mut kids = vec![];
+    for page in pages {
+        let is_png = page
+            .path
+            .extension()
+            .is_some_and(|v| v.eq_ignore_ascii_case("png"));
+        let (image, width, height) = if is_png {
+            png_image(&mut doc, &page.path)?
+        } else {
+            jpeg_image(&page.path)?
+        };
+        let image_id = doc.add_object(image);
+
+        let content = format!("q {width} 0 0 {height} 0 0 cm /Im0 Do Q");
+        let content_id = doc.add_object(Stream::new(dictionary! {}, content.into_bytes()));
+        let page_id = doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "MediaBox" => vec![0.into(), 0.into(), width.into(), height.into()],
+            "Contents" => content_id,
+            "Resources" => dictionary! {
+                "XObject" => dictionary! {
+                    "Im0" => image_id,
+                },
+            },
+        });
+        kids.push(Object::from(page_id));
+    }
+
+    let count = kids.len() as i64;
+    doc.objects.insert(
+        pages_id,
+        Object::Dictionary(dictionary! {
+            "Type" => "Pages",
+            "Kids" => kids,
+            "Count" => count,
+        }),
+    );
+    let catalog_id = doc.add_object(dictionary! {
+        "Type" => "Catalog",
+        "Pages" => pages_id,
+    });
+    doc.trailer.set("Root", catalog_id);
+
+    doc.save(output_filepath).map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+/// Returns the number of pages of the PDF
+///
+/// # Arguments
+///
+/// * `path` - PDF path
+pub fn page_count(path: &Path) -> Result<usize, String> {
+    let doc = Document::load(path).map_err(|e| e.to_string())?;
+
+    Ok(doc.get_pages().len())
+}
+
+/// Returns the image XObject of the JPEG without re-encoding, with its width and height
+///
+/// # Arguments
+///
+/// * `path` - JPEG path
+fn jpeg_image(path: &Path) -> Result<(Stream, u32, u32), String> {
+    let data = fs::read(path).map_err(|e| e.to_string())?;
+    let (precision, width, height, components, is_adobe) =
+        jpeg_frame(&data).ok_or_else(|| format!("{} is a broken JPEG", file_name(path)))?;
+
+    let mut dict = dictionary! {
+        "Type" => "XObject",
+        "Subtype" => "Image",
+        "Width" => width,
+        "Height" => height,
+        "BitsPerComponent" => precision,
+        "Filter" => "DCTDecode",
+    };
+    match components {
+        1 => dict.set("ColorSpace", "DeviceGray"),
+        3 => dict.set("ColorSpace", "DeviceRGB"),
+        4 => {
+            dict.set("ColorSpace", "DeviceCMYK");
+            // CMYK JPEG with the Adobe marker is stored inverted
+            if is_adobe {
+                dict.set(
+                    "Decode",
+                    [1, 0, 1, 0, 1, 0, 1, 0]
+                        .into_iter()
+                        .map(Object::from)
+                        .collect::<Vec<Object>>(),
+                );
+            }
+        }
+        v => return Err(format!("{} has {v} color components", file_name(path))),
+    }
+
+    Ok((
+        Stream::new(dict, data).with_compression(false),
+        width,
+        height,
+    ))
+}
+
+/// Returns the precision, width, height and number of components from the frame header of the JPEG,
+/// and whether the Adobe APP14 marker comes before the frame header
+///
+/// # Arguments
+///
+/// * `data` - JPEG data
+fn jpeg_frame(data: &[u8]) -> Option<(u8, u32, u32, u8, bool)> {
+    if data.get(..2)? != [0xFF, 0xD8] {
+        return None;
+    }
+
+    let mut is_adobe = false;
+    let mut i = 2;
+    loop {
+        if *data.get(i)? != 0xFF {
+            return None;
+        }
+        let marker = *data.get(i + 1)?;
+        // Fill bytes and markers without length
+        if marker == 0xFF {
+            i += 1;
+            continue;
+        }
+        if marker == 0x01 || (0xD0..=0xD8).contains(&marker) {
+            i += 2;
+            continue;
+        }
+
+        let length = u16::from_be_bytes([*data.get(i + 2)?, *data.get(i + 3)?]) as usize;
+        if marker == 0xEE && data.get(i + 4..i + 9) == Some(b"Adobe") {
+            is_adobe = true;
+        }
+        // SOF0 to SOF15 except DHT, JPG and DAC
+        if (0xC0..=0xCF).contains(&marker) && ![0xC4, 0xC8, 0xCC].contains(&marker) {
+            let header = data.get(i + 4..i + 10)?;
+            return Some((
+                header[0],
+                u16::from_be_bytes([header[3], header[4]]) as u32,
+                u16::from_be_bytes([header[1], header[2]]) as u32,
+                header[5],
+                is_adobe,
+            ));
+        }
+        i += 2 + length;
+    }
+}
+
+/// Returns the image XObject of the PNG compressed losslessly, with its width and height
+/// The alpha channel is added to the document as a soft mask
+///
+/// # Arguments
+///
+/// * `doc` - PDF document
+/// * `path` - PNG path
+fn png_image(doc: &mut Document, path: &Path) -> Result<(Stream, u32, u32), String> {
+    let file = File::open(path).map_err(|e| e.to_string())?;
+    let mut decoder = png::Decoder::new(BufReader::new(file));
+    // Palette and bit depths less than 8 are expanded to 8 bits per sample
+    decoder.set_transformations(png::Transformations::EXPAND);
+    let mut reader = decoder.read_info().map_err(|e| e.to_string())?;
+    let size = reader
+        .output_buffer_size()
+        .ok_or_else(|| format!("{} is too large", file_name(path)))?;
+    let mut buffer = vec![0; size];
+    let info = reader.next_frame(&mut buffer).map_err(|e| e.to_string())?;
+    buffer.truncate(info.line_size * info.height as usize);
+
+    let (color_space, has_alpha) = match info.color_type {
+        png::ColorType::Grayscale => ("DeviceGray", false),
+        png::ColorType::GrayscaleAlpha => ("DeviceGray", true),
+        png::ColorType::Rgb => ("DeviceRGB", false),
+        png::ColorType::Rgba => ("DeviceRGB", true),
+        png::ColorType::Indexed => return Err(format!("{} isn't expanded", file_name(path))),
+    };
+    let bits = match info.bit_depth {
+        png::BitDepth::Sixteen => 16,
+        _ => 8,
+    };
+
+    let image_dict = |color_space: &str, data: &[u8]| -> Result<Stream, String> {
+        let mut encoder = ZlibEncoder::new(vec![], Compression::best());
+        encoder.write_all(data).map_err(|e| e.to_string())?;
+        let data = encoder.finish().map_err(|e| e.to_string())?;
+        let dict = dictionary! {
+            "Type" => "XObject",
+            "Subtype" => "Image",
+            "Width" => info.width,
+            "Height" => info.height,
+            "ColorSpace" => color_space,
+            "BitsPerComponent" => bits,
+            "Filter" => "FlateDecode",
+        };
+        Ok(Stream::new(dict, data).with_compression(false))
+    };
+
+    let image = if has_alpha {
+        let channels = info.color_type.samples();
+        let (color, alpha) = split_alpha(&buffer, channels, bits as usize / 8);
+        let mask_id = doc.add_object(image_dict("DeviceGray", &alpha)?);
+        let mut image = image_dict(color_space, &color)?;
+        image.dict.set("SMask", mask_id);
+        image
+    } else {
+        image_dict(color_space, &buffer)?
+    };
+
+    Ok((image, info.width, info.height))
+}
+
+/// Returns the color samples and the alpha samples of the pixels
+///
+/// # Arguments
+///
+/// * `data` - Pixels whose last channel is alpha
+/// * `channels` - Number of channels including alpha
+/// * `bytes` - Bytes per sample
+fn split_alpha(data: &[u8], channels: usize, bytes: usize) -> (Vec<u8>, Vec<u8>) {
+    let pixel = channels * bytes;
+    let mut color = Vec::with_capacity(data.len() / channels * (channels - 1));
+    let mut alpha = Vec::with_capacity(data.len() / channels);
+    for v in data.chunks_exact(pixel) {
+        color.extend_from_slice(&v[..pixel - bytes]);
+        alpha.extend_from_slice(&v[pixel - bytes..]);
+    }
+
+    (color, alpha)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn jpeg_frame_should_be_found_after_other_segments() {
+        let data = [
+            0xFF, 0xD8, // SOI
+            0xFF, 0xE0, 0x00, 0x04, 0x00, 0x00, // APP0
+            0xFF, 0xC2, 0x00, 0x11, 0x08, 0x00, 0x20, 0x00, 0x10, 0x03, // SOF2
+        ];
+        assert_eq!(Some((8, 16, 32, 3, false)), jpeg_frame(&data));
+        assert_eq!(None, jpeg_frame(&[0x89, 0x50]));
+    }
+
+    #[test]
+    fn adobe_marker_should_be_detected() {
+        let data = [
+            0xFF, 0xD8, // SOI
+            0xFF, 0xEE, 0x00, 0x0E, b'A', b'd', b'o', b'b', b'e', 0x00, 0x64, 0x00, 0x00, 0x00,
+            0x00, 0x02, // APP14
+            0xFF, 0xC0, 0x00, 0x14, 0x08, 0x00, 0x20, 0x00, 0x10, 0x04, // SOF0
+        ];
+        assert_eq!(Some((8, 16, 32, 4, true)), jpeg_frame(&data));
+    }
+
+    #[test]
+    fn alpha_should_be_split_from_color() {
+        let data = [1, 2, 3, 4, 5, 6, 7, 8];
+        assert_eq!(
+            (vec![1, 2, 3, 5, 6, 7], vec![4, 8]),
+            split_alpha(&data, 4, 1)
+        );
+        assert_eq!(
+            (vec![1, 2, 5, 6], vec![3, 4, 7, 8]),
+            split_alpha(&data, 2, 2)
+        );
+    }
+}
@@ -1,10 +1,74 @@
 use crate::{
-    executor::utils::{ask, gen_random_path, get_progress_bar, is_dir, is_file, is_hidden},
-    params::rename::RenameParams,
+    executor::check_sequence::parse_numbers,
+    executor::filter::PathFilter,
+    executor::intent::{self, IntentEntry, Recovery},
+    executor::manifest::{self, ManifestEntry},
+    executor::pattern::{Pattern, PatternContext, DEFAULT_PATTERN},
+    executor::sort::{sort_files, sort_natural_paths},
+    executor::spread::detect_spreads,
+    executor::utils::{ask, file_name, get_files, get_progress_bar, is_dir, is_file, is_hidden},
+    params::rename::{CounterMode, OutputMode, RenameParams, SpreadMode},
 };
-use std::process;
-use std::{ffi::OsStr, fs, path::PathBuf};
-use walkdir::{DirEntry, WalkDir};
+use colored::Colorize;
+use indicatif::MultiProgress;
+use rayon::{prelude::*, ThreadPoolBuilder};
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::{
+    ffi::OsStr,
+    fs, io,
+    path::{Path, PathBuf},
+    process, thread,
+    time::Duration,
+};
+use uuid::Uuid;
+use walkdir::WalkDir;
+
+/// How to make the new filenames
+struct Naming {
+    pattern: Pattern,
+    /// If given, only the matching files are renamed and the pattern may refer to its capture groups
+    regex: Option<Regex>,
+}
+
+/// Files of a directory to be renamed
+struct DirectoryTask {
+    directory: PathBuf,
+    /// Sorted target files
+    files: Vec<PathBuf>,
+    /// Whether each file is a two-page spread
+    spreads: Vec<bool>,
+    /// Number of the first file of each counter, the others start from `--initial`
+    initials: HashMap<String, u32>,
+}
+
+/// Renames of a directory being planned, which carry their sidecars along
+struct PlanBuilder<'a> {
+    directory: &'a Path,
+    /// Sidecar files of the directory by their stem
+    sidecars: &'a HashMap<String, Vec<PathBuf>>,
+    claimed_sidecars: HashSet<&'a PathBuf>,
+    /// Original path of each new path to detect collisions
+    destinations: HashMap<PathBuf, &'a PathBuf>,
+    renames: Vec<(PathBuf, PathBuf)>,
+}
+
+/// Counters shared across the directories by `--global`
+#[derive(Default)]
+struct GlobalCounters {
+    /// Next number of each counter
+    counters: HashMap<String, u32>,
+    /// Subtree of the previous directory
+    subtree: Option<Option<PathBuf>>,
+}
+
+/// Rename plan for a directory
+struct RenamePlan {
+    /// Original path and final path of each target file
+    renames: Vec<(PathBuf, PathBuf)>,
+    /// Files to be renamed through staged names
+    entries: Vec<IntentEntry>,
+}
 
 /// Rename files in each directory
 ///
@@ -12,181 +76,1418 @@ use walkdir::{DirEntry, WalkDir};
 ///
 /// * `params` - Rename params
 pub fn execute(params: &RenameParams) {
-    let directories = WalkDir::new(&params.input_dir)
+    let naming = match get_naming(params) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("{}", e.red().bold());
+            process::exit(1);
+        }
+    };
+
+    let filter = PathFilter::new(&params.input_dir, &params.include, &params.exclude)
+        .expect("patterns are validated by the parser");
+
+    if let (Some(insert), Some(at)) = (&params.insert, params.at) {
+        execute_plan(
+            "insert into",
+            &format!(
+                "Inserted {} at {at:0width$}",
+                file_name(Path::new(insert)),
+                width = params.digit as usize
+            ),
+            |directory| plan_insert(directory, Path::new(insert), at, &filter, params),
+            params,
+        );
+        return;
+    }
+
+    if let Some(manifest_path) = &params.from_manifest {
+        execute_plan(
+            "apply the manifest to",
+            "Applied the manifest",
+            |directory| plan_manifest(directory, Path::new(manifest_path), params),
+            params,
+        );
+        return;
+    }
+
+    let mut walker = WalkDir::new(&params.input_dir);
+    if let Some(v) = params.min_depth {
+        walker = walker.min_depth(v);
+    }
+    if let Some(v) = params.max_depth {
+        walker = walker.max_depth(v);
+    }
+
+    // Excluded directories and the output directory are skipped with their subdirectories
+    let output_dir = params
+        .output_dir
+        .as_ref()
+        .and_then(|v| fs::canonicalize(v).ok());
+    let mut directories = walker
         .into_iter()
+        .filter_entry(|e| {
+            !(is_dir(e)
+                && (filter.is_excluded(e.path())
+                    || output_dir.is_some() && e.path().canonicalize().ok() == output_dir))
+        })
         .filter_map(Result::ok)
         .filter(|e| is_dir(e) && !is_hidden(e))
-        .collect::<Vec<DirEntry>>();
+        .map(|e| e.into_path())
+        .collect::<Vec<PathBuf>>();
+
+    sort_natural_paths(&mut directories);
 
     let directories_count = directories.len();
     println!("{directories_count} directories will be executed");
 
-    ask(params.yes);
+    if !params.dry_run {
+        ask(params.yes);
+    }
 
-    for entry in directories {
-        let mut files = WalkDir::new(entry.path())
-            .max_depth(1)
-            .into_iter()
-            .filter_map(Result::ok)
-            .filter(|v| {
-                is_file(v)
-                    && !is_hidden(v)
-                    && params.extensions.contains(
-                        &v.path()
-                            .extension()
-                            .unwrap_or_else(|| OsStr::new(""))
-                            .to_string_lossy()
-                            .to_lowercase(),
-                    )
-            })
-            .map(|v| v.into_path())
-            .collect::<Vec<PathBuf>>();
+    let run_id = Uuid::new_v4().to_string();
+    let extension_types = params.extensions.join(", ").to_lowercase();
+
+    let mut renamed_count = 0;
+    let mut error_directories = Vec::<(PathBuf, String)>::new();
+
+    // Recover and list the files of each directory first,
+    // since the global numbering needs the number of files of the previous directories
+    let mut tasks = vec![];
+    let mut counters = GlobalCounters::default();
+    for directory in directories {
+        let dir_name = file_name(&directory);
 
-        sort_natural(&mut files);
+        // Complete or roll back the interrupted run first
+        if let Err(e) = recover_directory(&directory, params) {
+            eprintln!("Can't rename {dir_name} directory: {e}");
+            error_directories.push((directory.clone(), e));
+            continue;
+        }
 
-        let dir_name = entry.file_name().to_string_lossy();
-        let extension_types = params.extensions.join(", ").to_lowercase();
+        let files = get_target_files(&directory, &filter, params);
         if files.is_empty() {
             println!("There are no {extension_types} files in {dir_name} directory");
             continue;
+        }
+
+        let spreads = match params.spreads {
+            Some(_) => detect_spreads(&files, params.spread_ratio),
+            None => vec![false; files.len()],
+        };
+
+        // Decide the initial number of this directory
+        let initials = if params.global {
+            counters.next(&directory, &files, &spreads, &naming, params)
         } else {
-            println!("Renaming {extension_types} files in {dir_name} directory");
+            HashMap::new()
+        };
+
+        tasks.push(DirectoryTask {
+            directory,
+            files,
+            spreads,
+            initials,
+        });
+    }
+
+    let multi = MultiProgress::new();
+    if params.dry_run {
+        for task in &tasks {
+            let dir_name = file_name(&task.directory);
+            match plan_rename(task, &naming, params) {
+                Ok(plan) => print_plan(&format!("{dir_name} directory"), &plan),
+                Err(e) => eprintln!("Can't rename {dir_name} directory: {e}"),
+            }
         }
+    } else {
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(params.jobs.unwrap_or(0) as usize)
+            .build()
+            .unwrap();
 
-        let bar = get_progress_bar(files.len() as u64);
-        bar.set_message(format!("Renaming {dir_name}"));
+        let bar = multi.add(get_progress_bar(tasks.len() as u64));
+        bar.set_message("Renaming directories");
 
-        let mut seq_index = params.initial;
-        for i in 0..files.len() {
-            let from_path = &files[i].clone();
-            let from_parent = from_path.parent().unwrap();
-            let extension = from_path
-                .extension()
-                .unwrap_or_else(|| OsStr::new(""))
-                .to_string_lossy()
-                .to_string();
+        let results = pool.install(|| {
+            tasks
+                .par_iter()
+                .map(|task| {
+                    let result = rename_directory(task, &naming, &run_id, &multi, params);
+                    bar.inc(1);
+                    (task.directory.clone(), result)
+                })
+                .collect::<Vec<(PathBuf, Result<bool, String>)>>()
+        });
 
-            let to_path = &from_parent.join(format!(
-                "{:0width$}.{ext}",
-                seq_index,
-                width = params.digit as usize,
-                ext = extension
-            ));
-
-            // If the file was already renamed, skip
-            if from_path == to_path {
-                seq_index += params.step as u32;
-                bar.inc(1);
-                continue;
+        bar.set_message("Rename complete");
+        bar.finish();
+
+        for (directory, result) in results {
+            match result {
+                Ok(true) => renamed_count += 1,
+                Ok(false) => {}
+                Err(e) => error_directories.push((directory, e)),
             }
+        }
+    }
 
-            // If the destination file already exists, rename the existed file to random filename
-            if to_path.exists() {
-                let random_path = gen_random_path(from_parent, &extension);
-                let index = match files.iter().position(|v| v == to_path) {
-                    Some(v) => v,
-                    None => {
-                        eprintln!("Unexpected error!");
-                        process::exit(1);
-                    }
-                };
-                files[index].clone_from(&random_path);
-                file_rename(to_path, &random_path);
+    // Rename the directories themselves after their files
+    let mut total_count = directories_count;
+    if params.dirs {
+        total_count += 1;
+        let input_dir = Path::new(&params.input_dir);
+        let input_name = file_name(input_dir);
+        match plan_directory_rename(input_dir, &filter, params) {
+            Ok(plan) if params.dry_run => {
+                print_plan(&format!("directories in {input_name} directory"), &plan)
             }
+            Ok(plan) if plan.entries.is_empty() => {
+                println!("All directories in {input_name} directory are already renamed")
+            }
+            Ok(plan) => {
+                println!("Renaming directories in {input_name} directory");
+                match apply_plan(input_dir, &run_id, plan, &multi, params) {
+                    Ok(_) => renamed_count += 1,
+                    Err(e) => error_directories.push((input_dir.to_path_buf(), e)),
+                }
+            }
+            Err(e) => {
+                eprintln!("Can't rename directories in {input_name} directory: {e}");
+                error_directories.push((input_dir.to_path_buf(), e));
+            }
+        }
+    }
+
+    if params.dry_run {
+        return;
+    }
+
+    // Show rename result
+    let skipped_count = total_count - renamed_count - error_directories.len();
+    println!(
+        "Rename result: {}/{}/{}/{} = {}/{}/{}/{}",
+        "Total".blue().bold(),
+        "Renamed".green().bold(),
+        "Skipped".yellow().bold(),
+        "Error".red().bold(),
+        format!("{total_count}").blue().bold(),
+        format!("{renamed_count}").green().bold(),
+        format!("{skipped_count}").yellow().bold(),
+        format!("{}", error_directories.len()).red().bold(),
+    );
 
-            file_rename(from_path, to_path);
-            seq_index += params.step as u32;
-            bar.inc(1);
+    // Show rename error directories
+    if !error_directories.is_empty() {
+        println!("{}", "The error directories are listed below".red().bold());
+        for (directory, error) in error_directories {
+            println!("{}", directory.display());
+            for line in error.lines() {
+                println!("  {line}");
+            }
         }
+        process::exit(1);
+    }
+}
 
-        bar.set_message(format!("Rename complete {dir_name}"));
-        bar.finish();
+/// Complete or roll back the interrupted rename of a directory
+/// In dry-run or with `--output-dir`, the directory isn't recovered and can't be planned,
+/// since its files aren't in the state which the plan would be applied to
+///
+/// # Arguments
+///
+/// * `directory` - Directory
+/// * `params` - Rename params
+fn recover_directory(directory: &Path, params: &RenameParams) -> Result<(), String> {
+    let dir_name = file_name(directory);
+    if params.output_dir.is_some() {
+        if intent::intent_path(directory).exists() {
+            return Err(
+                "The interrupted rename must be recovered by a run without --output-dir first"
+                    .to_string(),
+            );
+        }
+        return Ok(());
+    }
+    if params.dry_run {
+        if intent::intent_path(directory).exists() {
+            return Err(
+                "The interrupted rename must be recovered by a run without --dry-run before its plan is shown"
+                    .to_string(),
+            );
+        }
+        return Ok(());
+    }
+
+    match intent::recover(directory) {
+        Ok(Some(Recovery::RolledBack(v))) => {
+            println!("Rolled back {v} files of the interrupted rename in {dir_name} directory")
+        }
+        Ok(Some(Recovery::Completed(v))) => {
+            println!("Completed {v} files of the interrupted rename in {dir_name} directory")
+        }
+        Ok(Some(Recovery::Undone(v))) => {
+            println!("Completed {v} files of the interrupted undo in {dir_name} directory")
+        }
+        Ok(None) => {}
+        Err(e) => return Err(format!("Failed to recover the interrupted rename: {e}")),
     }
+
+    Ok(())
 }
 
-/// Rename file until success
+/// Rename the files of the target directory by a single plan
 ///
 /// # Arguments
 ///
-/// * `from_path` - From path
-/// * `to_path` - To path
-fn file_rename(from_path: &PathBuf, to_path: &PathBuf) {
-    loop {
-        if fs::rename(from_path, to_path).is_ok() {
-            break;
-        } else {
-            eprintln!("Error! Renaming {from_path:?} to {to_path:?}");
-            eprintln!("Try again...");
+/// * `action` - What is done to the directory such as `insert into`
+/// * `done` - Message shown on success
+/// * `plan` - Compute the plan of the directory
+/// * `params` - Rename params
+fn execute_plan(
+    action: &str,
+    done: &str,
+    plan: impl FnOnce(&Path) -> Result<RenamePlan, String>,
+    params: &RenameParams,
+) {
+    let directory = Path::new(&params.input_dir);
+    let dir_name = file_name(directory);
+
+    let plan = match recover_directory(directory, params).and_then(|_| plan(directory)) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!(
+                "{}",
+                format!("Can't {action} {dir_name} directory").red().bold()
+            );
+            eprintln!("{e}");
+            process::exit(1);
         }
+    };
+
+    print_plan(&format!("{dir_name} directory"), &plan);
+    if params.dry_run {
+        return;
     }
+
+    ask(params.yes);
+
+    let run_id = Uuid::new_v4().to_string();
+    let renames = plan.renames.clone();
+    let result = apply_plan(directory, &run_id, plan, &MultiProgress::new(), params)
+        .and_then(|_| write_manifest(directory, &renames, params));
+    if let Err(e) = result {
+        eprintln!(
+            "{}",
+            format!("Failed to {action} {dir_name} directory")
+                .red()
+                .bold()
+        );
+        eprintln!("{e}");
+        process::exit(1);
+    }
+
+    println!(
+        "{}",
+        format!("{done} in {dir_name} directory").green().bold()
+    );
 }
 
-fn sort_natural(files: &mut [PathBuf]) {
-    files.sort_by(|a, b| {
-        let a_key = generate_natural_sort_key(a.to_string_lossy().to_string().as_str());
-        let b_key = generate_natural_sort_key(b.to_string_lossy().to_string().as_str());
+/// Compute the renames of the target directory by a manifest without touching disk
+/// The files already renamed to their new names are left as they are
+///
+/// # Arguments
+///
+/// * `directory` - Directory containing the files
+/// * `manifest_path` - Manifest path
+/// * `params` - Rename params
+fn plan_manifest(
+    directory: &Path,
+    manifest_path: &Path,
+    params: &RenameParams,
+) -> Result<RenamePlan, String> {
+    let entries = manifest::read(manifest_path)?;
+    for entry in &entries {
+        for name in [&entry.original, &entry.new] {
+            if Path::new(name).file_name() != Some(OsStr::new(name)) {
+                return Err(format!("`{name}` in the manifest isn't a filename"));
+            }
+        }
+    }
 
-        let mut cmp_result = std::cmp::Ordering::Equal;
-        for (a_part, b_part) in a_key.iter().zip(b_key.iter()) {
-            let text_cmp_result = a_part.text.cmp(&b_part.text);
-            if text_cmp_result != std::cmp::Ordering::Equal {
-                cmp_result = text_cmp_result;
-                break;
+    let originals = entries
+        .iter()
+        .map(|v| directory.join(&v.original))
+        .collect::<Vec<PathBuf>>();
+    let sidecars = get_sidecars(directory, &originals, params);
+    let mut builder = PlanBuilder::new(directory, &sidecars);
+
+    for (entry, from_path) in entries.iter().zip(&originals) {
+        let to_path = directory.join(&entry.new);
+        if !from_path.exists() {
+            if to_path.exists() {
+                builder.keep(&to_path);
+                continue;
             }
+            return Err(format!("`{}` doesn't exist", entry.original));
+        }
 
-            let number_cmp_result = a_part.number.cmp(&b_part.number);
-            if number_cmp_result != std::cmp::Ordering::Equal {
-                cmp_result = number_cmp_result;
-                break;
+        // The content must be the same as the manifest says
+        if let Some(size) = entry.size {
+            let len = fs::metadata(from_path)
+                .map_err(|e| format!("Reading {from_path:?}: {e}"))?
+                .len();
+            if len != size {
+                return Err(format!(
+                    "`{}` has a different size from the manifest",
+                    entry.original
+                ));
+            }
+        }
+        if let Some(hash) = &entry.sha256 {
+            if !manifest::sha256(from_path)?.eq_ignore_ascii_case(hash) {
+                return Err(format!(
+                    "`{}` has a different hash from the manifest",
+                    entry.original
+                ));
             }
         }
 
-        cmp_result
-    })
+        builder.add(from_path, to_path)?;
+    }
+
+    builder.build(params)
+}
+
+/// Compute the renames to insert a file without touching disk
+/// The files named by numbers from the inserted number, including spreads such as `0012-0013.jpg`,
+/// are shifted up by the step and the others are left as they are
+///
+/// # Arguments
+///
+/// * `directory` - Directory containing the files
+/// * `insert` - File to insert
+/// * `at` - Number of the inserted file
+/// * `filter` - Include and exclude patterns
+/// * `params` - Rename params
+fn plan_insert(
+    directory: &Path,
+    insert: &Path,
+    at: u32,
+    filter: &PathFilter,
+    params: &RenameParams,
+) -> Result<RenamePlan, String> {
+    let insert = insert
+        .canonicalize()
+        .map_err(|e| format!("Reading `{}`: {e}", insert.display()))?;
+    if insert.parent() != directory.canonicalize().ok().as_deref() {
+        return Err(format!(
+            "`{}` must be in the target directory",
+            file_name(&insert)
+        ));
+    }
+    let insert = directory.join(file_name(&insert));
+
+    let files = get_target_files(directory, filter, params)
+        .into_iter()
+        .filter(|v| *v != insert)
+        .collect::<Vec<PathBuf>>();
+    let sidecars = get_sidecars(directory, &files, params);
+    let mut builder = PlanBuilder::new(directory, &sidecars);
+
+    let digit = params.digit as usize;
+    let step = params.step as u32;
+    for from_path in &files {
+        let stem = from_path
+            .file_stem()
+            .unwrap_or_else(|| OsStr::new(""))
+            .to_string_lossy()
+            .to_string();
+        let numbers = match parse_numbers(&stem) {
+            Some(v) if v.iter().any(|(v, _)| *v >= at) => {
+                v.into_iter().map(|(v, _)| v).collect::<Vec<u32>>()
+            }
+            _ => {
+                builder.keep(from_path);
+                continue;
+            }
+        };
+
+        let to_stem = numbers
+            .iter()
+            .map(|v| format!("{:0digit$}", v + step))
+            .collect::<Vec<String>>()
+            .join("-");
+        let to_path = directory.join(format!("{to_stem}.{}", get_extension(from_path, params)));
+        builder.add(from_path, to_path)?;
+    }
+
+    let to_path = directory.join(format!("{at:0digit$}.{}", get_extension(&insert, params)));
+    builder.add(&insert, to_path)?;
+
+    builder.build(params)
 }
 
-struct NaturalSortKeyPart {
-    text: String,
-    number: Option<usize>,
+/// Rename the files of a directory
+/// Returns true if any file was renamed
+///
+/// # Arguments
+///
+/// * `task` - Files of the directory
+/// * `naming` - How to make the new filenames
+/// * `run_id` - Run ID recorded in the journal
+/// * `multi` - Progress bars of the workers
+/// * `params` - Rename params
+fn rename_directory(
+    task: &DirectoryTask,
+    naming: &Naming,
+    run_id: &str,
+    multi: &MultiProgress,
+    params: &RenameParams,
+) -> Result<bool, String> {
+    let directory = task.directory.as_path();
+    let dir_name = file_name(directory);
+    let extension_types = params.extensions.join(", ").to_lowercase();
+
+    let plan = plan_rename(task, naming, params).inspect_err(|_| {
+        multi.suspend(|| eprintln!("Can't rename {dir_name} directory"));
+    })?;
+
+    if params.output_dir.is_some() {
+        let output = get_output_directory(directory, params);
+        multi.suspend(|| {
+            println!(
+                "Putting {extension_types} files in {dir_name} directory into {}",
+                output.display()
+            )
+        });
+        return export_plan(&output, plan, multi, params).map(|_| true);
+    }
+
+    // If all files were already renamed, skip
+    if plan.entries.is_empty() {
+        multi.suspend(|| {
+            println!("All {extension_types} files in {dir_name} directory are already renamed")
+        });
+        return Ok(false);
+    }
+
+    multi.suspend(|| println!("Renaming {extension_types} files in {dir_name} directory"));
+    let renames = plan.renames.clone();
+    apply_plan(directory, run_id, plan, multi, params)?;
+    write_manifest(directory, &renames, params)?;
+
+    Ok(true)
 }
 
-fn generate_natural_sort_key(s: &str) -> Vec<NaturalSortKeyPart> {
-    let mut key = vec![];
-    let mut last = 0;
-    for (start, end) in extract_number_positions(s) {
-        key.push(NaturalSortKeyPart {
-            text: s[last..start].to_string(),
-            number: s[start..end].parse::<usize>().ok(),
+/// Returns how to make the new filenames from the params
+///
+/// # Arguments
+///
+/// * `params` - Rename params
+fn get_naming(params: &RenameParams) -> Result<Naming, String> {
+    let (Some(regex), Some(replace)) = (&params.regex, &params.replace) else {
+        let pattern = Pattern::parse(params.pattern.as_deref().unwrap_or(DEFAULT_PATTERN))?;
+        return Ok(Naming {
+            pattern,
+            regex: None,
         });
-        last = end;
+    };
+
+    let regex = Regex::new(regex).map_err(|e| e.to_string())?;
+    let pattern = Pattern::parse_with_captures(replace)?;
+
+    // Check the capture groups used in the template exist
+    for name in pattern.capture_names() {
+        let exists = match name.parse::<usize>() {
+            Ok(i) => i < regex.captures_len(),
+            Err(_) => regex.capture_names().any(|v| v == Some(name)),
+        };
+        if !exists {
+            return Err(format!("`{{{name}}}` isn't a capture group of the regex"));
+        }
+    }
+
+    Ok(Naming {
+        pattern,
+        regex: Some(regex),
+    })
+}
+
+/// Returns the sorted target files directly under the given directory
+///
+/// # Arguments
+///
+/// * `directory` - Directory
+/// * `filter` - Include and exclude patterns
+/// * `params` - Rename params
+fn get_target_files(directory: &Path, filter: &PathFilter, params: &RenameParams) -> Vec<PathBuf> {
+    let mut files = get_files(directory, &params.extensions, |v| filter.is_included(v));
+
+    sort_files(&mut files, params.sort, params.reverse);
+
+    files
+}
+
+/// Compute the renames of the directories directly under the given directory to sequential numbers
+///
+/// # Arguments
+///
+/// * `input_dir` - Directory containing the directories
+/// * `filter` - Include and exclude patterns
+/// * `params` - Rename params
+fn plan_directory_rename(
+    input_dir: &Path,
+    filter: &PathFilter,
+    params: &RenameParams,
+) -> Result<RenamePlan, String> {
+    let mut directories = WalkDir::new(input_dir)
+        .min_depth(1)
+        .max_depth(1)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|v| is_dir(v) && !is_hidden(v) && filter.is_included(v.path()))
+        .map(|v| v.into_path())
+        .collect::<Vec<PathBuf>>();
+
+    sort_natural_paths(&mut directories);
+
+    let renames = directories
+        .into_iter()
+        .enumerate()
+        .map(|(i, v)| {
+            let number = params.initial + i as u32 * params.step as u32;
+            let to_path =
+                input_dir.join(format!("{:0width$}", number, width = params.digit as usize));
+            (v, to_path)
+        })
+        .collect::<Vec<(PathBuf, PathBuf)>>();
+
+    let entries = intent::plan(input_dir, &renames)?;
+
+    Ok(RenamePlan { renames, entries })
+}
+
+/// Rename the files of the plan with a progress bar
+///
+/// # Arguments
+///
+/// * `directory` - Directory containing the files
+/// * `run_id` - Run ID recorded in the journal
+/// * `plan` - Rename plan
+/// * `multi` - Progress bars of the workers
+/// * `params` - Rename params
+fn apply_plan(
+    directory: &Path,
+    run_id: &str,
+    plan: RenamePlan,
+    multi: &MultiProgress,
+    params: &RenameParams,
+) -> Result<(), String> {
+    let dir_name = file_name(directory);
+    let bar = multi.add(get_progress_bar(plan.entries.len() as u64 * 2));
+    bar.set_message(format!("Renaming {dir_name}"));
+
+    let result = intent::apply(directory, run_id, plan.entries, |from_path, to_path| {
+        file_rename(from_path, to_path, multi, params)?;
+        bar.inc(1);
+        Ok(())
+    });
+
+    bar.finish_and_clear();
+    multi.remove(&bar);
+
+    result
+}
+
+/// Put the renamed files of the plan into the output directory, leaving the originals as they are
+///
+/// # Arguments
+///
+/// * `output` - Output directory of the directory
+/// * `plan` - Rename plan
+/// * `multi` - Progress bars of the workers
+/// * `params` - Rename params
+fn export_plan(
+    output: &Path,
+    plan: RenamePlan,
+    multi: &MultiProgress,
+    params: &RenameParams,
+) -> Result<(), String> {
+    let files = plan
+        .renames
+        .into_iter()
+        .map(|(from_path, to_path)| (from_path, output.join(file_name(&to_path))))
+        .collect::<Vec<(PathBuf, PathBuf)>>();
+
+    // Never overwrite the files of the previous output
+    if let Some((_, to_path)) = files.iter().find(|(_, v)| v.exists()) {
+        return Err(format!("`{}` already exists", to_path.display()));
     }
 
-    key.push(NaturalSortKeyPart {
-        text: s[last..].to_string(),
-        number: None,
+    fs::create_dir_all(output).map_err(|e| format!("Creating {output:?}: {e}"))?;
+
+    let bar = multi.add(get_progress_bar(files.len() as u64));
+    bar.set_message(format!("Putting {}", file_name(output)));
+
+    let result = files.iter().try_for_each(|(from_path, to_path)| {
+        file_output(from_path, to_path, multi, params)
+            .map_err(|e| format!("Putting {from_path:?} as {to_path:?}: {e}"))?;
+        bar.inc(1);
+        Ok(())
     });
 
-    key
+    bar.finish_and_clear();
+    multi.remove(&bar);
+
+    result.and_then(|_| write_manifest(output, &files, params))
+}
+
+/// Write the manifest of the renamed files to the directory if requested
+///
+/// # Arguments
+///
+/// * `directory` - Directory containing the renamed files
+/// * `renames` - Original path and current path of each file
+/// * `params` - Rename params
+fn write_manifest(
+    directory: &Path,
+    renames: &[(PathBuf, PathBuf)],
+    params: &RenameParams,
+) -> Result<(), String> {
+    let Some(format) = params.manifest else {
+        return Ok(());
+    };
+
+    let entries = renames
+        .iter()
+        .map(|(from_path, to_path)| ManifestEntry::new(from_path, to_path))
+        .collect::<Result<Vec<ManifestEntry>, String>>()?;
+
+    manifest::write(directory, format, &entries)
+}
+
+/// Returns the directory in the mirrored tree under the output directory
+///
+/// # Arguments
+///
+/// * `directory` - Directory under the input directory
+/// * `params` - Rename params
+fn get_output_directory(directory: &Path, params: &RenameParams) -> PathBuf {
+    let output_dir = Path::new(params.output_dir.as_deref().unwrap_or_default());
+    match directory.strip_prefix(&params.input_dir) {
+        Ok(v) => output_dir.join(v),
+        Err(_) => output_dir.to_path_buf(),
+    }
+}
+
+/// Returns the directory directly under the input directory which contains the given directory
+/// The input directory itself belongs to its own subtree
+///
+/// # Arguments
+///
+/// * `directory` - Directory
+/// * `input_dir` - Input directory
+fn get_subtree(directory: &Path, input_dir: &str) -> Option<PathBuf> {
+    directory
+        .strip_prefix(input_dir)
+        .ok()
+        .and_then(|v| v.components().next())
+        .map(|v| PathBuf::from(v.as_os_str()))
 }
 
-fn extract_number_positions(s: &str) -> Vec<(usize, usize)> {
-    let mut positions = vec![];
-    let mut start_index = None;
+impl GlobalCounters {
+    /// Returns the initial numbers of the directory and advances the counters by its files
+    /// The counters are reset when the subtree changes with `--reset-per-subtree`
+    ///
+    /// # Arguments
+    ///
+    /// * `directory` - Directory
+    /// * `files` - Target files
+    /// * `spreads` - Whether each file is a spread
+    /// * `naming` - How to make the new filenames
+    /// * `params` - Rename params
+    fn next(
+        &mut self,
+        directory: &Path,
+        files: &[PathBuf],
+        spreads: &[bool],
+        naming: &Naming,
+        params: &RenameParams,
+    ) -> HashMap<String, u32> {
+        let subtree = get_subtree(directory, &params.input_dir);
+        if params.reset_per_subtree && self.subtree.as_ref() != Some(&subtree) {
+            self.counters.clear();
+        }
+        self.subtree = Some(subtree);
+
+        let initials = self.counters.clone();
+        advance_counters(&mut self.counters, files, spreads, naming, params);
+        initials
+    }
+}
 
-    for (i, c) in s.char_indices() {
-        if c.is_ascii_digit() {
-            if start_index.is_none() {
-                start_index = Some(i);
+/// Advance the counters by the numbers the files take
+/// Spreads take two numbers and the files not matching the regex take none
+///
+/// # Arguments
+///
+/// * `counters` - Next number of each counter
+/// * `files` - Target files
+/// * `spreads` - Whether each file is a spread
+/// * `naming` - How to make the new filenames
+/// * `params` - Rename params
+fn advance_counters(
+    counters: &mut HashMap<String, u32>,
+    files: &[PathBuf],
+    spreads: &[bool],
+    naming: &Naming,
+    params: &RenameParams,
+) {
+    for (file, &is_spread) in files.iter().zip(spreads) {
+        if naming
+            .regex
+            .as_ref()
+            .is_some_and(|regex| !regex.is_match(&file_name(file)))
+        {
+            continue;
+        }
+
+        let pages = if is_spread { 2 } else { 1 };
+        *counters
+            .entry(get_counter(file, params))
+            .or_insert(params.initial) += pages * params.step as u32;
+    }
+}
+
+/// Returns the counter of the given file
+/// All files share a counter unless counted per extension
+///
+/// # Arguments
+///
+/// * `path` - File
+/// * `params` - Rename params
+fn get_counter(path: &Path, params: &RenameParams) -> String {
+    match params.counter {
+        CounterMode::Shared => String::new(),
+        CounterMode::PerExtension => get_extension(path, params).to_lowercase(),
+    }
+}
+
+/// Returns the extension of the new filename
+///
+/// # Arguments
+///
+/// * `path` - File
+/// * `params` - Rename params
+fn get_extension(path: &Path, params: &RenameParams) -> String {
+    let extension = path
+        .extension()
+        .unwrap_or_else(|| OsStr::new(""))
+        .to_string_lossy()
+        .to_string();
+
+    if params.normalize_ext {
+        normalize_extension(&extension)
+    } else {
+        extension
+    }
+}
+
+/// Returns the canonical form of the given extension, which is lowercase and `jpg` for `jpeg`
+///
+/// # Arguments
+///
+/// * `extension` - Extension
+fn normalize_extension(extension: &str) -> String {
+    let extension = extension.to_lowercase();
+    match extension.as_str() {
+        "jpeg" | "jpe" => "jpg".to_string(),
+        _ => extension,
+    }
+}
+
+/// Returns the sidecar files of the directory by their stem
+/// Target files themselves are never sidecars
+///
+/// # Arguments
+///
+/// * `directory` - Directory
+/// * `files` - Target files of the directory
+/// * `params` - Rename params
+fn get_sidecars(
+    directory: &Path,
+    files: &[PathBuf],
+    params: &RenameParams,
+) -> HashMap<String, Vec<PathBuf>> {
+    let mut sidecars = HashMap::<String, Vec<PathBuf>>::new();
+    if params.sidecars.is_empty() {
+        return sidecars;
+    }
+
+    let files = files.iter().collect::<HashSet<&PathBuf>>();
+    for entry in WalkDir::new(directory)
+        .max_depth(1)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|v| is_file(v) && !is_hidden(v) && !files.contains(&v.path().to_path_buf()))
+    {
+        let path = entry.into_path();
+        let extension = path
+            .extension()
+            .unwrap_or_else(|| OsStr::new(""))
+            .to_string_lossy()
+            .to_lowercase();
+        if params.sidecars.contains(&extension) {
+            let stem = path
+                .file_stem()
+                .unwrap_or_else(|| OsStr::new(""))
+                .to_string_lossy()
+                .to_string();
+            sidecars.entry(stem).or_default().push(path);
+        }
+    }
+
+    sidecars
+}
+
+/// Compute the renames of the given files by the pattern without touching disk
+///
+/// # Arguments
+///
+/// * `task` - Files of the directory
+/// * `naming` - How to make the new filenames
+/// * `params` - Rename params
+fn plan_rename(
+    task: &DirectoryTask,
+    naming: &Naming,
+    params: &RenameParams,
+) -> Result<RenamePlan, String> {
+    let directory = task.directory.as_path();
+    let dir_name = file_name(directory);
+    let parent_name = directory.parent().map(file_name).unwrap_or_default();
+
+    let sidecars = get_sidecars(directory, &task.files, params);
+    let mut builder = PlanBuilder::new(directory, &sidecars);
+
+    let mut counters = task.initials.clone();
+    for (from_path, &is_spread) in task.files.iter().zip(&task.spreads) {
+        let extension = get_extension(from_path, params);
+        let stem = from_path
+            .file_stem()
+            .unwrap_or_else(|| OsStr::new(""))
+            .to_string_lossy()
+            .to_string();
+
+        // The files not matching the regex are left as they are
+        let name = file_name(from_path);
+        let captures = match &naming.regex {
+            Some(regex) => match regex.captures(&name) {
+                Some(v) => Some(v),
+                None => {
+                    builder.keep(from_path);
+                    continue;
+                }
+            },
+            None => None,
+        };
+
+        let seq_index = counters
+            .entry(get_counter(from_path, params))
+            .or_insert(params.initial);
+        let to_path = directory.join(naming.pattern.render(&PatternContext {
+            dir: &dir_name,
+            parent: &parent_name,
+            number: *seq_index,
+            number_end: match params.spreads {
+                Some(SpreadMode::Combined) if is_spread => Some(*seq_index + params.step as u32),
+                _ => None,
+            },
+            digit: params.digit as usize,
+            orig: &stem,
+            ext: &extension,
+            mtime: fs::metadata(from_path).and_then(|v| v.modified()).ok(),
+            captures: captures.as_ref(),
+        }));
+        // Spreads take the numbers of two pages
+        *seq_index += if is_spread { 2 } else { 1 } * params.step as u32;
+
+        builder.add(from_path, to_path)?;
+    }
+
+    builder.build(params)
+}
+
+impl<'a> PlanBuilder<'a> {
+    /// Returns an empty builder
+    ///
+    /// # Arguments
+    ///
+    /// * `directory` - Directory containing the files
+    /// * `sidecars` - Sidecar files of the directory by their stem
+    fn new(directory: &'a Path, sidecars: &'a HashMap<String, Vec<PathBuf>>) -> Self {
+        PlanBuilder {
+            directory,
+            sidecars,
+            claimed_sidecars: HashSet::new(),
+            destinations: HashMap::new(),
+            renames: vec![],
+        }
+    }
+
+    /// Add a file left as it is
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - File
+    fn keep(&mut self, path: &Path) {
+        self.renames.push((path.to_path_buf(), path.to_path_buf()));
+    }
+
+    /// Add the rename of a file and its sidecars
+    ///
+    /// # Arguments
+    ///
+    /// * `from_path` - Original path
+    /// * `to_path` - New path
+    fn add(&mut self, from_path: &'a PathBuf, to_path: PathBuf) -> Result<(), String> {
+        let stem = from_path
+            .file_stem()
+            .unwrap_or_else(|| OsStr::new(""))
+            .to_string_lossy()
+            .to_string();
+        let name = file_name(from_path);
+        let to_stem = to_path
+            .file_stem()
+            .unwrap_or_else(|| OsStr::new(""))
+            .to_string_lossy()
+            .to_string();
+        let to_name = file_name(&to_path);
+
+        // Sidecars such as `IMG_0001.xmp` or `IMG_0001.jpg.xmp` follow the new name of the file
+        let mut sidecar_renames = vec![];
+        for (key, to_key) in [(&stem, &to_stem), (&name, &to_name)] {
+            for sidecar in self.sidecars.get(key).into_iter().flatten() {
+                if self.claimed_sidecars.insert(sidecar) {
+                    let sidecar_extension = sidecar
+                        .extension()
+                        .unwrap_or_else(|| OsStr::new(""))
+                        .to_string_lossy();
+                    sidecar_renames.push((
+                        sidecar,
+                        self.directory.join(format!("{to_key}.{sidecar_extension}")),
+                    ));
+                }
             }
-        } else if let Some(start) = start_index {
-            positions.push((start, i));
-            start_index = None;
         }
+
+        for (from_path, to_path) in [(from_path, to_path)].into_iter().chain(sidecar_renames) {
+            if let Some(v) = self.destinations.insert(to_path.clone(), from_path) {
+                return Err(format!(
+                    "`{}` and `{}` would be renamed to the same `{}`",
+                    file_name(v),
+                    file_name(from_path),
+                    file_name(&to_path)
+                ));
+            }
+            self.renames.push((from_path.clone(), to_path));
+        }
+
+        Ok(())
     }
 
-    if let Some(start) = start_index {
-        positions.push((start, s.len()))
+    /// Returns the plan of the added renames
+    /// The files are staged only when renamed in place
+    ///
+    /// # Arguments
+    ///
+    /// * `params` - Rename params
+    fn build(self, params: &RenameParams) -> Result<RenamePlan, String> {
+        let entries = match params.output_dir {
+            Some(_) => vec![],
+            None => intent::plan(self.directory, &self.renames)?,
+        };
+
+        Ok(RenamePlan {
+            renames: self.renames,
+            entries,
+        })
     }
+}
 
-    positions
+/// Print the old to new filename table of the plan
+///
+/// # Arguments
+///
+/// * `title` - What is renamed
+/// * `plan` - Rename plan
+fn print_plan(title: &str, plan: &RenamePlan) {
+    println!("Rename plan for {title}");
+
+    let names = plan
+        .renames
+        .iter()
+        .map(|(from, to)| (file_name(from), file_name(to)))
+        .collect::<Vec<(String, String)>>();
+    let width = names
+        .iter()
+        .map(|(from, _)| from.chars().count())
+        .max()
+        .unwrap_or(0);
+
+    for (from, to) in names {
+        if from == to {
+            println!("  {from:<width$}  ->  {to} (unchanged)");
+        } else {
+            println!("  {from:<width$}  ->  {to}");
+        }
+    }
+
+    if !plan.entries.is_empty() {
+        println!(
+            "  ({} renames will go through temporary names to avoid collisions)",
+            plan.entries.len()
+        );
+    }
+}
+
+/// Rename file with bounded retries
+///
+/// # Arguments
+///
+/// * `from_path` - From path
+/// * `to_path` - To path
+/// * `multi` - Progress bars suspended while showing the retries
+/// * `params` - Rename params
+fn file_rename(
+    from_path: &Path,
+    to_path: &Path,
+    multi: &MultiProgress,
+    params: &RenameParams,
+) -> io::Result<()> {
+    with_retries("Renaming", from_path, to_path, multi, params, || {
+        fs::rename(from_path, to_path)
+    })
+}
+
+/// Copy, hard link or reflink file to the output directory with bounded retries
+///
+/// # Arguments
+///
+/// * `from_path` - From path
+/// * `to_path` - To path
+/// * `multi` - Progress bars suspended while showing the retries
+/// * `params` - Rename params
+fn file_output(
+    from_path: &Path,
+    to_path: &Path,
+    multi: &MultiProgress,
+    params: &RenameParams,
+) -> io::Result<()> {
+    match params.output_mode {
+        OutputMode::Copy => with_retries("Copying", from_path, to_path, multi, params, || {
+            fs::copy(from_path, to_path).map(|_| ())
+        }),
+        OutputMode::Hardlink => with_retries("Linking", from_path, to_path, multi, params, || {
+            fs::hard_link(from_path, to_path)
+        }),
+        OutputMode::Reflink => with_retries("Cloning", from_path, to_path, multi, params, || {
+            reflink_copy::reflink(from_path, to_path)
+        }),
+    }
+}
+
+/// Run the file operation with bounded retries
+/// The delay between the retries doubles on each failure
+///
+/// # Arguments
+///
+/// * `action` - Name of the operation shown on failure
+/// * `from_path` - From path
+/// * `to_path` - To path
+/// * `multi` - Progress bars suspended while showing the retries
+/// * `params` - Rename params
+/// * `operation` - File operation
+fn with_retries(
+    action: &str,
+    from_path: &Path,
+    to_path: &Path,
+    multi: &MultiProgress,
+    params: &RenameParams,
+    operation: impl Fn() -> io::Result<()>,
+) -> io::Result<()> {
+    let mut delay = Duration::from_millis(params.retry_delay);
+    let mut retries = 0;
+    loop {
+        match operation() {
+            Ok(_) => return Ok(()),
+            // Retrying is pointless if the filesystem doesn't support the operation
+            Err(e) if retries < params.retries && e.kind() != io::ErrorKind::Unsupported => {
+                multi.suspend(|| {
+                    eprintln!("Error! {action} {from_path:?} to {to_path:?}: {e}");
+                    eprintln!("Try again in {}ms...", delay.as_millis());
+                });
+                thread::sleep(delay);
+                delay *= 2;
+                retries += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::params::rename::ManifestFormat;
+    use clap::Parser;
+    use tempfile::TempDir;
+
+    fn rename_params(input_dir: &Path, args: &[&str]) -> RenameParams {
+        let input_dir = input_dir.to_string_lossy().to_string();
+        RenameParams::parse_from([&["rename", input_dir.as_str()], args].concat())
+    }
+
+    #[test]
+    fn dotted_directory_names_should_be_sorted_by_full_name() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("vol.1 (extra)")).unwrap();
+        fs::create_dir(dir.path().join("Vol 2")).unwrap();
+        let params = rename_params(dir.path(), &["--dirs"]);
+        let filter = PathFilter::new(&params.input_dir, &[], &[]).unwrap();
+
+        let plan = plan_directory_rename(dir.path(), &filter, &params).unwrap();
+        let renames = plan
+            .renames
+            .iter()
+            .map(|(from, to)| (file_name(from), file_name(to)))
+            .collect::<Vec<(String, String)>>();
+        assert_eq!(
+            vec![
+                ("Vol 2".to_string(), "0001".to_string()),
+                ("vol.1 (extra)".to_string(), "0002".to_string()),
+            ],
+            renames
+        );
+    }
+
+    #[test]
+    fn only_included_directories_should_be_renamed_with_dirs() {
+        let dir = TempDir::new().unwrap();
+        for name in ["Vol 1", "Vol 2", "Extra"] {
+            fs::create_dir(dir.path().join(name)).unwrap();
+        }
+        let params = rename_params(dir.path(), &["--dirs", "--include", "Vol*"]);
+        let filter = PathFilter::new(&params.input_dir, &params.include, &[]).unwrap();
+
+        let plan = plan_directory_rename(dir.path(), &filter, &params).unwrap();
+        assert_eq!(
+            vec![
+                ("Vol 1".to_string(), "0001".to_string()),
+                ("Vol 2".to_string(), "0002".to_string()),
+            ],
+            rename_names(&plan.renames)
+        );
+    }
+
+    fn touch(directory: &Path, names: &[&str]) -> Vec<PathBuf> {
+        names
+            .iter()
+            .map(|name| {
+                let path = directory.join(name);
+                fs::write(&path, name).unwrap();
+                path
+            })
+            .collect()
+    }
+
+    fn rename_names(renames: &[(PathBuf, PathBuf)]) -> Vec<(String, String)> {
+        let mut names = renames
+            .iter()
+            .map(|(from, to)| (file_name(from), file_name(to)))
+            .collect::<Vec<(String, String)>>();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn sidecars_should_match_by_stem_or_full_name() {
+        let dir = TempDir::new().unwrap();
+        let files = touch(dir.path(), &["IMG_0001.jpg", "IMG_0002.jpg"]);
+        touch(
+            dir.path(),
+            &["IMG_0001.xmp", "IMG_0002.jpg.xmp", "other.xmp"],
+        );
+        let params = rename_params(dir.path(), &["--sidecars", "xmp"]);
+
+        let sidecars = get_sidecars(dir.path(), &files, &params);
+        let mut builder = PlanBuilder::new(dir.path(), &sidecars);
+        builder.add(&files[0], dir.path().join("0001.jpg")).unwrap();
+        builder.add(&files[1], dir.path().join("0002.jpg")).unwrap();
+
+        assert_eq!(
+            vec![
+                ("IMG_0001.jpg".to_string(), "0001.jpg".to_string()),
+                ("IMG_0001.xmp".to_string(), "0001.xmp".to_string()),
+                ("IMG_0002.jpg".to_string(), "0002.jpg".to_string()),
+                ("IMG_0002.jpg.xmp".to_string(), "0002.jpg.xmp".to_string()),
+            ],
+            rename_names(&builder.renames)
+        );
+    }
+
+    #[test]
+    fn sidecar_should_be_claimed_once() {
+        let dir = TempDir::new().unwrap();
+        let files = touch(dir.path(), &["IMG_0001.jpg", "IMG_0001.png"]);
+        touch(dir.path(), &["IMG_0001.xmp"]);
+        let params = rename_params(dir.path(), &["--sidecars", "xmp", "-e", "jpg", "-e", "png"]);
+
+        let sidecars = get_sidecars(dir.path(), &files, &params);
+        let mut builder = PlanBuilder::new(dir.path(), &sidecars);
+        builder.add(&files[0], dir.path().join("0001.jpg")).unwrap();
+        builder.add(&files[1], dir.path().join("0002.png")).unwrap();
+
+        assert_eq!(
+            vec![
+                ("IMG_0001.jpg".to_string(), "0001.jpg".to_string()),
+                ("IMG_0001.png".to_string(), "0002.png".to_string()),
+                ("IMG_0001.xmp".to_string(), "0001.xmp".to_string()),
+            ],
+            rename_names(&builder.renames)
+        );
+    }
+
+    #[test]
+    fn subtree_should_be_first_directory_under_input_dir() {
+        assert_eq!(
+            Some(PathBuf::from("a")),
+            get_subtree(Path::new("lib/a/b"), "lib")
+        );
+        assert_eq!(None, get_subtree(Path::new("lib"), "lib"));
+    }
+
+    fn global_initials(args: &[&str]) -> Vec<Option<u32>> {
+        let dir = TempDir::new().unwrap();
+        let params = rename_params(dir.path(), args);
+        let naming = get_naming(&params).unwrap();
+        let mut counters = GlobalCounters::default();
+        [("a/1", 2), ("a/2", 1), ("b/1", 2)]
+            .iter()
+            .map(|&(directory, count)| {
+                let directory = dir.path().join(directory);
+                let files = (0..count)
+                    .map(|i| directory.join(format!("{i}.jpg")))
+                    .collect::<Vec<PathBuf>>();
+                let spreads = vec![false; files.len()];
+                let initials = counters.next(&directory, &files, &spreads, &naming, &params);
+                initials.get("").copied()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn global_counters_should_continue_across_directories() {
+        assert_eq!(vec![None, Some(3), Some(4)], global_initials(&["-g"]));
+    }
+
+    #[test]
+    fn global_counters_should_be_reset_per_subtree() {
+        assert_eq!(
+            vec![None, Some(3), None],
+            global_initials(&["-g", "--reset-per-subtree"])
+        );
+    }
+
+    fn write_manifest_of(directory: &Path, content: &str) -> PathBuf {
+        let new = directory.join("0001.jpg");
+        fs::write(&new, content).unwrap();
+        let entry = ManifestEntry::new(&directory.join("a.jpg"), &new).unwrap();
+        manifest::write(directory, ManifestFormat::Csv, &[entry]).unwrap();
+        manifest::manifest_path(directory, ManifestFormat::Csv)
+    }
+
+    #[test]
+    fn written_manifest_should_be_applied() {
+        let written = TempDir::new().unwrap();
+        let manifest_path = write_manifest_of(written.path(), "page");
+
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("a.jpg"), "page").unwrap();
+        let params = rename_params(dir.path(), &[]);
+        let plan = plan_manifest(dir.path(), &manifest_path, &params).unwrap();
+        assert_eq!(
+            vec![("a.jpg".to_string(), "0001.jpg".to_string())],
+            rename_names(&plan.renames)
+        );
+
+        // Already renamed files are kept
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("0001.jpg"), "page").unwrap();
+        let plan = plan_manifest(dir.path(), &manifest_path, &params).unwrap();
+        assert_eq!(
+            vec![("0001.jpg".to_string(), "0001.jpg".to_string())],
+            rename_names(&plan.renames)
+        );
+    }
+
+    #[test]
+    fn different_content_from_manifest_should_be_error() {
+        let written = TempDir::new().unwrap();
+        let manifest_path = write_manifest_of(written.path(), "page");
+
+        let dir = TempDir::new().unwrap();
+        let params = rename_params(dir.path(), &[]);
+        fs::write(dir.path().join("a.jpg"), "pages").unwrap();
+        let e = plan_manifest(dir.path(), &manifest_path, &params).err();
+        assert_eq!(
+            Some("`a.jpg` has a different size from the manifest".to_string()),
+            e
+        );
+
+        fs::write(dir.path().join("a.jpg"), "PAGE").unwrap();
+        let e = plan_manifest(dir.path(), &manifest_path, &params).err();
+        assert_eq!(
+            Some("`a.jpg` has a different hash from the manifest".to_string()),
+            e
+        );
+
+        fs::remove_file(dir.path().join("a.jpg")).unwrap();
+        let e = plan_manifest(dir.path(), &manifest_path, &params).err();
+        assert_eq!(Some("`a.jpg` doesn't exist".to_string()), e);
+    }
+
+    /// Writes the intent log of `a.jpg` staged as `tmp-1.jpg` next to `b.jpg`
+    fn interrupted(directory: &Path) {
+        touch(directory, &["tmp-1.jpg", "b.jpg"]);
+        fs::write(
+            intent::intent_path(directory),
+            r#"{"run_id":"run","phase":"staging","entries":[{"original":"a.jpg","staged":"tmp-1.jpg","target":"0002.jpg"}]}"#,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn interrupted_directory_should_not_be_exported() {
+        let dir = TempDir::new().unwrap();
+        interrupted(dir.path());
+        let output = TempDir::new().unwrap();
+        let output_dir = output.path().to_string_lossy().to_string();
+        let params = rename_params(dir.path(), &["-o", &output_dir]);
+
+        assert!(recover_directory(dir.path(), &params).is_err());
+        assert!(dir.path().join("tmp-1.jpg").exists());
+        assert!(intent::intent_path(dir.path()).exists());
+    }
+
+    #[test]
+    fn interrupted_directory_should_not_be_planned_in_dry_run() {
+        let dir = TempDir::new().unwrap();
+        interrupted(dir.path());
+        let params = rename_params(dir.path(), &["--dry-run"]);
+
+        assert!(recover_directory(dir.path(), &params).is_err());
+        assert!(dir.path().join("tmp-1.jpg").exists());
+        assert!(intent::intent_path(dir.path()).exists());
+    }
 }
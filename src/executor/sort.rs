@@ -0,0 +1,301 @@
+use crate::params::rename::SortKey;
+use exif::{In, Reader, Tag, Value};
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::mem;
+use std::path::{Path, PathBuf};
+use unicode_normalization::UnicodeNormalization;
+
+/// Sort files by the given key
+/// Files with the same key are kept in natural order
+///
+/// # Arguments
+///
+/// * `files` - Files to sort
+/// * `key` - Sort key
+/// * `reverse` - Sort in descending order
+pub fn sort_files(files: &mut [PathBuf], key: SortKey, reverse: bool) {
+    sort_natural(files);
+
+    match key {
+        SortKey::Natural => {}
+        SortKey::Lexical => files.sort(),
+        SortKey::Mtime => {
+            files.sort_by_cached_key(|v| fs::metadata(v).and_then(|v| v.modified()).ok())
+        }
+        SortKey::Ctime => files.sort_by_cached_key(|v| get_ctime(v)),
+        SortKey::Size => files.sort_by_cached_key(|v| fs::metadata(v).map(|v| v.len()).ok()),
+        // Files without EXIF date come last
+        SortKey::ExifDate => files.sort_by_cached_key(|v| {
+            let date = get_exif_date(v);
+            (date.is_none(), date)
+        }),
+    }
+
+    if reverse {
+        files.reverse();
+    }
+}
+
+/// Returns the status changed time of the file as `(seconds, nanoseconds)` since the unix epoch
+///
+/// # Arguments
+///
+/// * `path` - File path
+#[cfg(unix)]
+fn get_ctime(path: &Path) -> Option<(i64, i64)> {
+    use std::os::unix::fs::MetadataExt;
+
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.ctime(), metadata.ctime_nsec()))
+}
+
+/// Returns the created time of the file as `(seconds, nanoseconds)` since the unix epoch,
+/// since there is no status changed time on the platform
+///
+/// # Arguments
+///
+/// * `path` - File path
+#[cfg(not(unix))]
+fn get_ctime(path: &Path) -> Option<(i64, i64)> {
+    let created = fs::metadata(path).and_then(|v| v.created()).ok()?;
+    let duration = created.duration_since(std::time::UNIX_EPOCH).ok()?;
+    Some((duration.as_secs() as i64, duration.subsec_nanos() as i64))
+}
+
+/// Returns the original date of the image from EXIF as `(year, month, day, hour, minute, second)`
+///
+/// # Arguments
+///
+/// * `path` - Image path
+fn get_exif_date(path: &Path) -> Option<(u16, u8, u8, u8, u8, u8)> {
+    let file = File::open(path).ok()?;
+    let exif = Reader::new()
+        .read_from_container(&mut BufReader::new(file))
+        .ok()?;
+
+    [Tag::DateTimeOriginal, Tag::DateTimeDigitized, Tag::DateTime]
+        .iter()
+        .find_map(|&tag| match &exif.get_field(tag, In::PRIMARY)?.value {
+            Value::Ascii(v) => {
+                let v = exif::DateTime::from_ascii(v.first()?).ok()?;
+                Some((v.year, v.month, v.day, v.hour, v.minute, v.second))
+            }
+            _ => None,
+        })
+}
+
+/// Sort files naturally by the file stems regardless of the extensions, then by the file names
+/// Directories must be sorted by `sort_natural_paths` as a dot in their names isn't an extension
+///
+/// # Arguments
+///
+/// * `files` - Files to sort
+pub fn sort_natural(files: &mut [PathBuf]) {
+    files.sort_by_cached_key(|v| {
+        let stem = v.file_stem().unwrap_or_default().to_string_lossy();
+        let name = v.file_name().unwrap_or_default().to_string_lossy();
+        (
+            NaturalSortKey::new(&stem),
+            NaturalSortKey::new(&name),
+            v.clone(),
+        )
+    });
+}
+
+/// Sort paths naturally by each component of the paths
+///
+/// # Arguments
+///
+/// * `paths` - Paths to sort
+pub fn sort_natural_paths(paths: &mut [PathBuf]) {
+    paths.sort_by_cached_key(|v| {
+        let key = v
+            .components()
+            .map(|v| NaturalSortKey::new(&v.as_os_str().to_string_lossy()))
+            .collect::<Vec<NaturalSortKey>>();
+        (key, v.clone())
+    });
+}
+
+/// Key to compare strings naturally
+/// The string is normalized by NFKC and lowercased,
+/// so full-width digits are compared as numbers and case differences are ignored
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+struct NaturalSortKey(Vec<NaturalSortKeyPart>);
+
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+struct NaturalSortKeyPart {
+    text: String,
+    number: Option<NaturalSortNumber>,
+}
+
+/// Number of arbitrary length
+/// Compared by the number of significant digits first, then the digits themselves
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+struct NaturalSortNumber {
+    significant_len: usize,
+    significant_digits: String,
+    leading_zeros: usize,
+}
+
+impl NaturalSortKey {
+    /// Returns the natural sort key of the given string
+    ///
+    /// # Arguments
+    ///
+    /// * `s` - String
+    fn new(s: &str) -> Self {
+        let normalized = s.nfkc().collect::<String>().to_lowercase();
+
+        let mut parts = vec![];
+        let mut text = String::new();
+        let mut digits = String::new();
+        for c in normalized.chars() {
+            if c.is_ascii_digit() {
+                digits.push(c);
+                continue;
+            }
+
+            if !digits.is_empty() {
+                parts.push(NaturalSortKeyPart {
+                    text: mem::take(&mut text),
+                    number: Some(NaturalSortNumber::new(&mem::take(&mut digits))),
+                });
+            }
+            text.push(c);
+        }
+
+        parts.push(NaturalSortKeyPart {
+            text,
+            number: (!digits.is_empty()).then(|| NaturalSortNumber::new(&digits)),
+        });
+
+        NaturalSortKey(parts)
+    }
+}
+
+impl NaturalSortNumber {
+    /// Returns the number of the given ASCII digits
+    ///
+    /// # Arguments
+    ///
+    /// * `digits` - ASCII digits
+    fn new(digits: &str) -> Self {
+        let significant_digits = digits.trim_start_matches('0');
+        NaturalSortNumber {
+            significant_len: significant_digits.len(),
+            significant_digits: significant_digits.to_string(),
+            leading_zeros: digits.len() - significant_digits.len(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(names: &[&str]) -> Vec<String> {
+        let mut files = names.iter().map(PathBuf::from).collect::<Vec<PathBuf>>();
+        sort_natural(&mut files);
+        files
+            .iter()
+            .map(|v| v.to_string_lossy().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn numbers_should_be_compared_by_value() {
+        assert_eq!(
+            vec!["p1.jpg", "p2.jpg", "p10.jpg"],
+            sorted(&["p10.jpg", "p2.jpg", "p1.jpg"])
+        );
+    }
+
+    #[test]
+    fn full_width_numbers_should_be_compared_by_value() {
+        assert_eq!(
+            vec!["第２話.jpg", "第3話.jpg", "第１２話.jpg"],
+            sorted(&["第１２話.jpg", "第3話.jpg", "第２話.jpg"])
+        );
+    }
+
+    #[test]
+    fn case_should_be_ignored() {
+        assert_eq!(
+            vec!["a1.jpg", "B1.jpg", "c1.jpg"],
+            sorted(&["c1.jpg", "B1.jpg", "a1.jpg"])
+        );
+    }
+
+    #[test]
+    fn long_numbers_should_not_overflow() {
+        assert_eq!(
+            vec!["99999999999999999999.jpg", "100000000000000000000.jpg"],
+            sorted(&["100000000000000000000.jpg", "99999999999999999999.jpg"])
+        );
+    }
+
+    #[test]
+    fn file_names_should_be_compared_instead_of_paths() {
+        assert_eq!(vec!["b/1.jpg", "a/2.jpg"], sorted(&["a/2.jpg", "b/1.jpg"]));
+    }
+
+    #[test]
+    fn stems_should_be_compared_before_extensions() {
+        assert_eq!(
+            vec!["p1.png", "p1-2.jpg", "p2.jpg"],
+            sorted(&["p1-2.jpg", "p2.jpg", "p1.png"])
+        );
+    }
+
+    #[test]
+    fn paths_should_be_compared_by_components() {
+        let mut paths = ["a/ch10", "a-b/ch1", "a/ch2"]
+            .iter()
+            .map(PathBuf::from)
+            .collect::<Vec<PathBuf>>();
+        sort_natural_paths(&mut paths);
+        assert_eq!(
+            vec![
+                PathBuf::from("a/ch2"),
+                PathBuf::from("a/ch10"),
+                PathBuf::from("a-b/ch1")
+            ],
+            paths
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn ctime_should_be_status_changed_time() {
+        use std::os::unix::fs::PermissionsExt;
+        use std::thread;
+        use std::time::{Duration, SystemTime};
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let a = dir.path().join("a.jpg");
+        let b = dir.path().join("b.jpg");
+        let now = SystemTime::now();
+        for (path, age) in [(&a, 100), (&b, 50)] {
+            fs::write(path, "").unwrap();
+            File::options()
+                .write(true)
+                .open(path)
+                .unwrap()
+                .set_modified(now - Duration::from_secs(age))
+                .unwrap();
+        }
+        // The status of b.jpg is changed before a.jpg, while a.jpg was modified before b.jpg
+        for path in [&b, &a] {
+            thread::sleep(Duration::from_millis(20));
+            fs::set_permissions(path, fs::Permissions::from_mode(0o600)).unwrap();
+        }
+
+        let mut files = vec![a.clone(), b.clone()];
+        sort_files(&mut files, SortKey::Ctime, false);
+        assert_eq!(vec![b.clone(), a.clone()], files);
+        sort_files(&mut files, SortKey::Mtime, false);
+        assert_eq!(vec![a, b], files);
+    }
+}
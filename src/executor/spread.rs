@@ -0,0 +1,66 @@
+use std::path::PathBuf;
+
+/// Returns whether each image is a two-page spread
+/// An image is a spread if it is wider than the median width of the images by the given ratio
+///
+/// # Arguments
+///
+/// * `files` - Images in a directory
+/// * `ratio` - Width ratio to the median width
+pub fn detect_spreads(files: &[PathBuf], ratio: f64) -> Vec<bool> {
+    let widths = files
+        .iter()
+        .map(|v| imagesize::size(v).ok().map(|v| v.width))
+        .collect::<Vec<Option<usize>>>();
+
+    find_spreads(&widths, ratio)
+}
+
+/// Returns whether each width is a spread compared to the median width
+/// Images whose width is unknown are never spreads
+///
+/// # Arguments
+///
+/// * `widths` - Width of each image
+/// * `ratio` - Width ratio to the median width
+fn find_spreads(widths: &[Option<usize>], ratio: f64) -> Vec<bool> {
+    let mut known = widths.iter().flatten().copied().collect::<Vec<usize>>();
+    if known.is_empty() {
+        return vec![false; widths.len()];
+    }
+
+    // Median is robust to the spreads themselves
+    known.sort_unstable();
+    let median = known[known.len() / 2] as f64;
+
+    widths
+        .iter()
+        .map(|v| v.is_some_and(|v| v as f64 >= median * ratio))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn images_twice_as_wide_should_be_spreads() {
+        let widths = [
+            Some(1000),
+            Some(1010),
+            Some(2000),
+            None,
+            Some(990),
+            Some(1400),
+        ];
+        assert_eq!(
+            vec![false, false, true, false, false, false],
+            find_spreads(&widths, 1.5)
+        );
+    }
+
+    #[test]
+    fn unknown_widths_should_not_be_spreads() {
+        assert_eq!(vec![false, false], find_spreads(&[None, None], 1.5));
+    }
+}
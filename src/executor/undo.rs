@@ -0,0 +1,353 @@
+use crate::executor::intent::{self, Recovery};
+use crate::executor::journal::{self, journal_path, JournalMove, JournalRun};
+use crate::executor::utils::{ask, file_name, is_dir, is_hidden};
+use crate::params::undo::UndoParams;
+use colored::Colorize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use walkdir::WalkDir;
+
+/// Undo the last rename across the directories
+///
+/// # Arguments
+///
+/// * `params` - Undo params
+pub fn execute(params: &UndoParams) {
+    // Undo the children first, since the directories themselves may have been renamed
+    let directories = WalkDir::new(&params.input_dir)
+        .contents_first(true)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|e| {
+            is_dir(e)
+                && !is_hidden(e)
+                && (journal_path(e.path()).exists() || intent::intent_path(e.path()).exists())
+        })
+        .map(|e| e.into_path())
+        .collect::<Vec<PathBuf>>();
+
+    let run_id = match get_last_run_id(&directories) {
+        Ok(Some(v)) => v,
+        Ok(None) => {
+            eprintln!(
+                "{}",
+                "There are no renamed directories to be undone".red().bold()
+            );
+            eprintln!("Abort...");
+            process::exit(0);
+        }
+        Err(e) => {
+            eprintln!("{}", "Failed to read the journals".red().bold());
+            eprintln!("{e}");
+            process::exit(1);
+        }
+    };
+
+    let directories = directories
+        .into_iter()
+        .filter(|v| has_run(v, &run_id))
+        .collect::<Vec<PathBuf>>();
+
+    println!("{} directories will be undone", directories.len());
+
+    ask(params.yes);
+
+    let mut error_count = 0;
+    for directory in directories {
+        let dir_name = file_name(&directory);
+        match recover_directory(&directory).and_then(|_| undo_directory(&directory, &run_id)) {
+            Ok(v) => println!("Reverted {v} moves in {dir_name} directory"),
+            Err(e) => {
+                eprintln!(
+                    "{}",
+                    format!("Failed to undo {dir_name} directory").red().bold()
+                );
+                eprintln!("{e}");
+                error_count += 1;
+            }
+        }
+    }
+
+    if error_count > 0 {
+        process::exit(1);
+    }
+}
+
+/// Returns the run ID to be undone
+/// The interrupted run is continued first, otherwise the run finished last across the directories is chosen
+///
+/// # Arguments
+///
+/// * `directories` - Directories with a journal or an intent log
+fn get_last_run_id(directories: &[PathBuf]) -> Result<Option<String>, String> {
+    let mut last = None;
+    for directory in directories {
+        // The interrupted undo or the rename being completed is the last run
+        if let Some(log) = intent::read(directory)? {
+            if log.undo || log.phase == intent::Phase::Finishing {
+                return Ok(Some(log.run_id));
+            }
+        }
+
+        if let Some(run) = journal::read(directory)?.runs.pop() {
+            if last.as_ref().is_none_or(|(time, _)| run.time > *time) {
+                last = Some((run.time, run.id));
+            }
+        }
+    }
+
+    Ok(last.map(|(_, id)| id))
+}
+
+/// Returns true if the last run of the directory or its interrupted run has the given run ID
+///
+/// # Arguments
+///
+/// * `directory` - Directory
+/// * `run_id` - Run ID
+fn has_run(directory: &Path, run_id: &str) -> bool {
+    let in_journal =
+        journal::read(directory).is_ok_and(|v| v.runs.last().is_some_and(|v| v.id == run_id));
+    let in_intent = intent::read(directory).is_ok_and(|v| v.is_some_and(|v| v.run_id == run_id));
+
+    in_journal || in_intent
+}
+
+/// Complete or roll back the interrupted run of the directory before undoing
+///
+/// # Arguments
+///
+/// * `directory` - Directory
+fn recover_directory(directory: &Path) -> Result<(), String> {
+    let dir_name = file_name(directory);
+    match intent::recover(directory)? {
+        Some(Recovery::RolledBack(v)) => {
+            println!("Rolled back {v} files of the interrupted rename in {dir_name} directory")
+        }
+        Some(Recovery::Completed(v)) => {
+            println!("Completed {v} files of the interrupted rename in {dir_name} directory")
+        }
+        Some(Recovery::Undone(v)) => {
+            println!("Completed {v} files of the interrupted undo in {dir_name} directory")
+        }
+        None => {}
+    }
+
+    Ok(())
+}
+
+/// Replay the runs of the given run ID in the journal backwards through an intent log
+/// and returns the number of reverted moves
+///
+/// # Arguments
+///
+/// * `directory` - Directory
+/// * `run_id` - Run ID to be undone
+fn undo_directory(directory: &Path, run_id: &str) -> Result<usize, String> {
+    // A rename may record several runs in a directory, such as files and directories
+    let journal = journal::read(directory)?;
+    let start = journal
+        .runs
+        .iter()
+        .rposition(|v| v.id != run_id)
+        .map_or(0, |i| i + 1);
+    let run = JournalRun {
+        id: run_id.to_string(),
+        time: None,
+        moves: journal.runs[start..]
+            .iter()
+            .flat_map(|v| v.moves.clone())
+            .collect(),
+    };
+    if run.moves.is_empty() {
+        return Ok(0);
+    }
+    journal::check_undoable(directory, &run)?;
+
+    let renames = get_reverse_renames(directory, &run.moves);
+    let entries = intent::plan(directory, &renames)?;
+    if entries.is_empty() {
+        journal::remove_last_runs(directory, run_id)?;
+    } else {
+        intent::undo(directory, run_id, entries, |from, to| fs::rename(from, to))?;
+    }
+
+    Ok(run.moves.len())
+}
+
+/// Returns the renames from the current name to the original name of each file moved by the moves
+///
+/// # Arguments
+///
+/// * `directory` - Directory
+/// * `moves` - Moves in the order they were done
+fn get_reverse_renames(directory: &Path, moves: &[JournalMove]) -> Vec<(PathBuf, PathBuf)> {
+    // Original name of each file by its current name
+    let mut originals = HashMap::<&str, &str>::new();
+    for v in moves {
+        let original = originals.remove(v.from.as_str()).unwrap_or(&v.from);
+        originals.insert(&v.to, original);
+    }
+
+    let mut renames = originals
+        .into_iter()
+        .filter(|(current, original)| current != original)
+        .map(|(current, original)| (directory.join(current), directory.join(original)))
+        .collect::<Vec<(PathBuf, PathBuf)>>();
+    renames.sort();
+    renames
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::intent;
+    use std::fs::File;
+    use std::path::PathBuf;
+    use std::time::{Duration, SystemTime};
+    use tempfile::TempDir;
+
+    fn rename(directory: &Path, run_id: &str, renames: &[(&str, &str)]) {
+        let renames = renames
+            .iter()
+            .map(|(from, to)| (directory.join(from), directory.join(to)))
+            .collect::<Vec<(PathBuf, PathBuf)>>();
+        let entries = intent::plan(directory, &renames).unwrap();
+        intent::apply(directory, run_id, entries, |from, to| fs::rename(from, to)).unwrap();
+    }
+
+    fn read(directory: &Path, name: &str) -> String {
+        fs::read_to_string(directory.join(name)).unwrap()
+    }
+
+    /// Returns a directory whose `a.jpg` and `b.jpg` were swapped by a run
+    fn swapped() -> TempDir {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("a.jpg"), "a").unwrap();
+        fs::write(dir.path().join("b.jpg"), "b").unwrap();
+        rename(dir.path(), "run", &[("a.jpg", "b.jpg"), ("b.jpg", "a.jpg")]);
+        assert_eq!("a", read(dir.path(), "b.jpg"));
+        dir
+    }
+
+    #[test]
+    fn undo_should_restore_names_after_collision_shuffle() {
+        let dir = swapped();
+
+        assert_eq!(4, undo_directory(dir.path(), "run").unwrap());
+        assert_eq!("a", read(dir.path(), "a.jpg"));
+        assert_eq!("b", read(dir.path(), "b.jpg"));
+        assert!(!journal_path(dir.path()).exists());
+    }
+
+    #[test]
+    fn undo_should_refuse_changed_size() {
+        let dir = swapped();
+        fs::write(dir.path().join("b.jpg"), "changed").unwrap();
+
+        assert!(undo_directory(dir.path(), "run").is_err());
+        assert_eq!("b", read(dir.path(), "a.jpg"));
+    }
+
+    #[test]
+    fn undo_should_refuse_changed_mtime() {
+        let dir = swapped();
+        File::options()
+            .write(true)
+            .open(dir.path().join("b.jpg"))
+            .unwrap()
+            .set_modified(SystemTime::now() + Duration::from_secs(60))
+            .unwrap();
+
+        assert!(undo_directory(dir.path(), "run").is_err());
+        assert_eq!("b", read(dir.path(), "a.jpg"));
+    }
+
+    #[test]
+    fn undo_should_refuse_taken_original_name() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("a.jpg"), "a").unwrap();
+        rename(dir.path(), "run", &[("a.jpg", "0001.jpg")]);
+        fs::write(dir.path().join("a.jpg"), "new").unwrap();
+
+        assert!(undo_directory(dir.path(), "run").is_err());
+        assert_eq!("a", read(dir.path(), "0001.jpg"));
+        assert_eq!("new", read(dir.path(), "a.jpg"));
+    }
+
+    #[test]
+    fn runs_of_same_id_should_be_undone_together() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("a.jpg"), "a").unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        rename(dir.path(), "first", &[("a.jpg", "b.jpg")]);
+        // Files and directories renamed by one run with --dirs
+        rename(dir.path(), "second", &[("b.jpg", "0001.jpg")]);
+        rename(dir.path(), "second", &[("sub", "0001")]);
+
+        assert_eq!(4, undo_directory(dir.path(), "second").unwrap());
+        assert_eq!("a", read(dir.path(), "b.jpg"));
+        assert!(dir.path().join("sub").is_dir());
+
+        let journal = journal::read(dir.path()).unwrap();
+        assert_eq!(
+            vec!["first"],
+            journal.runs.iter().map(|v| &v.id).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn only_last_run_across_directories_should_be_undone() {
+        let dir = TempDir::new().unwrap();
+        let ch1 = dir.path().join("ch1");
+        let ch2 = dir.path().join("ch2");
+        fs::create_dir(&ch1).unwrap();
+        fs::create_dir(&ch2).unwrap();
+        fs::write(ch1.join("a.jpg"), "a").unwrap();
+        fs::write(ch2.join("a.jpg"), "a").unwrap();
+        rename(&ch1, "first", &[("a.jpg", "0001.jpg")]);
+        rename(&ch2, "second", &[("a.jpg", "0001.jpg")]);
+
+        let directories = vec![ch1.clone(), ch2.clone()];
+        assert_eq!(
+            Some("second".to_string()),
+            get_last_run_id(&directories).unwrap()
+        );
+        assert!(!has_run(&ch1, "second"));
+        assert!(has_run(&ch2, "second"));
+    }
+
+    #[test]
+    fn interrupted_undo_should_be_completed_by_recovery() {
+        let dir = swapped();
+        let journal = journal::read(dir.path()).unwrap();
+        let renames = get_reverse_renames(dir.path(), &journal.runs[0].moves);
+        let entries = intent::plan(dir.path(), &renames).unwrap();
+
+        // Fail right after staging the files
+        let mut count = 0;
+        let result = intent::undo(dir.path(), "run", entries, |from, to| {
+            count += 1;
+            if count > 2 {
+                return Err(std::io::Error::other("interrupted"));
+            }
+            fs::rename(from, to)
+        });
+        assert!(result.is_err());
+        assert_eq!(
+            Some("run".to_string()),
+            get_last_run_id(&[dir.path().to_path_buf()]).unwrap()
+        );
+
+        assert!(matches!(
+            intent::recover(dir.path()),
+            Ok(Some(Recovery::Undone(2)))
+        ));
+        assert_eq!("a", read(dir.path(), "a.jpg"));
+        assert_eq!("b", read(dir.path(), "b.jpg"));
+        assert!(!journal_path(dir.path()).exists());
+        assert_eq!(0, undo_directory(dir.path(), "run").unwrap());
+    }
+}
@@ -1,10 +1,11 @@
 use indicatif::{ProgressBar, ProgressState, ProgressStyle};
 use question::{Answer, Question};
+use std::ffi::OsStr;
 use std::fmt::Write;
 use std::path::{Path, PathBuf};
 use std::process;
 use uuid::Uuid;
-use walkdir::DirEntry;
+use walkdir::{DirEntry, WalkDir};
 
 /// Returns true if the given entry is file
 ///
@@ -44,32 +45,85 @@ pub fn is_parent(v: &Path, parent: &str) -> bool {
 }
 
 /// Returns true if the given entry has the given extension
+/// The extension may have several dots such as `tar.gz`
 ///
 /// # Arguments
 ///
 /// * `extension` - Extension
 /// * `path` - Path
 pub fn have_extension(extension: &str, path: &Path) -> bool {
-    if let Some(v) = path.extension() {
+    if let Some(v) = path.file_name() {
         if let Some(v) = v.to_str() {
-            return v == extension;
+            return v
+                .strip_suffix(extension)
+                .and_then(|v| v.strip_suffix('.'))
+                .is_some_and(|v| !v.is_empty());
         }
     }
 
     false
 }
 
+/// Returns the files directly under the given directory which have one of the given extensions
+/// Hidden files are skipped, and the extensions are compared in lowercase
+///
+/// # Arguments
+///
+/// * `directory` - Directory
+/// * `extensions` - Lowercase extensions
+/// * `is_target` - Returns true if the file is a target
+pub fn get_files(
+    directory: &Path,
+    extensions: &[String],
+    is_target: impl Fn(&Path) -> bool,
+) -> Vec<PathBuf> {
+    WalkDir::new(directory)
+        .max_depth(1)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|v| {
+            is_file(v)
+                && !is_hidden(v)
+                && extensions.contains(
+                    &v.path()
+                        .extension()
+                        .unwrap_or_else(|| OsStr::new(""))
+                        .to_string_lossy()
+                        .to_lowercase(),
+                )
+                && is_target(v.path())
+        })
+        .map(|v| v.into_path())
+        .collect()
+}
+
+/// Returns the file name of the given path as string
+///
+/// # Arguments
+///
+/// * `path` - Path
+pub fn file_name(path: &Path) -> String {
+    path.file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string()
+}
+
 /// Returns random path that does not exist in given parent path
 ///
 /// # Arguments
 ///
 /// * `parent` - Parent path
-/// * `ext` - Extension
+/// * `ext` - Extension, or empty for no extension
 pub fn gen_random_path(parent: &Path, ext: &str) -> PathBuf {
     let mut random_path;
     loop {
         let uuid = Uuid::new_v4().to_string();
-        random_path = parent.join(format!("{uuid}.{ext}"));
+        random_path = if ext.is_empty() {
+            parent.join(uuid)
+        } else {
+            parent.join(format!("{uuid}.{ext}"))
+        };
         if !random_path.exists() {
             break;
         }
@@ -78,6 +132,25 @@ pub fn gen_random_path(parent: &Path, ext: &str) -> PathBuf {
     random_path
 }
 
+/// Returns the civil date as `(year, month, day)` of the given days since 1970-01-01
+///
+/// # Arguments
+///
+/// * `days` - Days since the unix epoch
+pub fn civil_date(days: i64) -> (i64, i64, i64) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    (year, month, day)
+}
+
 /// Ask the user to execute or not
 ///
 /// # Arguments
@@ -113,3 +186,28 @@ pub fn get_progress_bar(length: u64) -> ProgressBar {
 
     pb
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn files_should_be_filtered_by_extension_and_visibility() {
+        let dir = TempDir::new().unwrap();
+        for name in ["a.jpg", "b.JPG", "c.png", ".d.jpg", "e.jpg"] {
+            fs::write(dir.path().join(name), "").unwrap();
+        }
+        fs::create_dir(dir.path().join("sub.jpg")).unwrap();
+        fs::write(dir.path().join("sub.jpg/f.jpg"), "").unwrap();
+
+        let extensions = vec!["jpg".to_string()];
+        let mut files = get_files(dir.path(), &extensions, |v| !v.ends_with("e.jpg"))
+            .iter()
+            .map(|v| file_name(v))
+            .collect::<Vec<String>>();
+        files.sort();
+        assert_eq!(vec!["a.jpg", "b.JPG"], files);
+    }
+}
@@ -14,9 +14,15 @@ struct Cli {
 #[derive(Subcommand)]
 enum Commands {
     /// Rename files in each directory to sequential number
-    Rename(params::rename::RenameParams),
+    Rename(Box<params::rename::RenameParams>),
     /// Compress files in each directory
     Compress(params::compress::CompressParams),
+    /// Undo the last rename under the target directory
+    Undo(params::undo::UndoParams),
+    /// Check the sequential numbers of the files in each directory
+    CheckSequence(params::check_sequence::CheckSequenceParams),
+    /// Merge the front pages and the back pages of duplex scans into one sequence
+    Interleave(params::interleave::InterleaveParams),
 }
 
 fn main() {
@@ -28,6 +34,15 @@ fn main() {
         Some(Commands::Compress(v)) => {
             executor::compress::execute(v);
         }
+        Some(Commands::Undo(v)) => {
+            executor::undo::execute(v);
+        }
+        Some(Commands::CheckSequence(v)) => {
+            executor::check_sequence::execute(v);
+        }
+        Some(Commands::Interleave(v)) => {
+            executor::interleave::execute(v);
+        }
         None => eprintln!("No subcommand provided!\nCheck the subcommands with `rimg -h`"),
     }
 }
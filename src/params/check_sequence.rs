@@ -0,0 +1,74 @@
+use crate::validation;
+use clap::{value_parser, Parser};
+
+#[derive(Parser)]
+/// Params for check-sequence subcommand
+pub struct CheckSequenceParams {
+    #[arg(
+        value_parser = validation::filepath::dir_exists,
+        help = "Target directory"
+    )]
+    pub input_dir: String,
+
+    #[arg(
+        short,
+        long,
+        default_value_t = 4,
+        value_parser = value_parser!(u8).range(1..=6),
+        help = "Number of digits expected"
+    )]
+    pub digit: u8,
+
+    #[arg(
+        short,
+        long,
+        default_values = vec!["jpg", "jpeg"],
+        value_parser = validation::filepath::extension_check,
+        help = "Target file extension"
+    )]
+    pub extensions: Vec<String>,
+
+    #[arg(
+        short,
+        long,
+        default_value_t = 1,
+        value_parser = value_parser!(u32).range(0..),
+        help = "Initial number expected"
+    )]
+    pub initial: u32,
+
+    #[arg(
+        short,
+        long,
+        default_value_t = 1,
+        value_parser = value_parser!(u8).range(1..),
+        help = "Number of steps expected between each files"
+    )]
+    pub step: u8,
+
+    #[arg(
+        long,
+        help = "Minimum depth of the directories to check, the target directory is depth 0"
+    )]
+    pub min_depth: Option<usize>,
+
+    #[arg(
+        long,
+        help = "Maximum depth of the directories to check, the target directory is depth 0"
+    )]
+    pub max_depth: Option<usize>,
+
+    #[arg(
+        long,
+        value_parser = validation::filepath::glob_check,
+        help = "Check only the files matching the glob patterns by name or relative path, or in the directories matching them"
+    )]
+    pub include: Vec<String>,
+
+    #[arg(
+        long,
+        value_parser = validation::filepath::glob_check,
+        help = "Skip the directories and files matching the glob patterns by name or relative path"
+    )]
+    pub exclude: Vec<String>,
+}
@@ -1,6 +1,15 @@
 use crate::constants::file::RAR_EXTENSION;
 use crate::validation;
-use clap::Parser;
+use clap::{value_parser, Parser, ValueEnum};
+
+/// Reading direction of the pages
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ReadingDirection {
+    /// Left to right
+    Ltr,
+    /// Right to left such as Japanese manga
+    Rtl,
+}
 
 #[derive(Parser)]
 /// Params for compress subcommand
@@ -28,6 +37,22 @@ pub struct CompressParams {
     )]
     pub format_type: String,
 
+    #[arg(
+        long,
+        default_value_t = 3,
+        value_parser = value_parser!(i32).range(1..=22),
+        help = "Compression level of tar.zst"
+    )]
+    pub zstd_level: i32,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = ReadingDirection::Ltr,
+        help = "Reading direction of cbz, cbr and epub"
+    )]
+    pub direction: ReadingDirection,
+
     #[arg(
         short,
         long,
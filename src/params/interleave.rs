@@ -0,0 +1,76 @@
+use crate::validation;
+use clap::{value_parser, Parser};
+
+#[derive(Parser)]
+/// Params for interleave subcommand
+pub struct InterleaveParams {
+    #[arg(
+        value_parser = validation::filepath::dir_exists,
+        help = "Directory of the front pages"
+    )]
+    pub fronts: String,
+
+    #[arg(
+        value_parser = validation::filepath::dir_exists,
+        help = "Directory of the back pages"
+    )]
+    pub backs: String,
+
+    #[arg(short, long, help = "Output directory, created if it doesn't exist")]
+    pub output_dir: String,
+
+    #[arg(
+        short,
+        long,
+        help = "Reverse the order of the back pages, as scanned by turning the stack over"
+    )]
+    pub reverse_backs: bool,
+
+    #[arg(
+        short,
+        long,
+        default_value_t = 4,
+        value_parser = value_parser!(u8).range(1..=6),
+        help = "Number of digits for renaming"
+    )]
+    pub digit: u8,
+
+    #[arg(
+        short,
+        long,
+        default_values = vec!["jpg", "jpeg"],
+        value_parser = validation::filepath::extension_check,
+        help = "Target file extension"
+    )]
+    pub extensions: Vec<String>,
+
+    #[arg(
+        short,
+        long,
+        default_value_t = 1,
+        value_parser = value_parser!(u32).range(0..),
+        help = "Initial number"
+    )]
+    pub initial: u32,
+
+    #[arg(
+        short,
+        long,
+        default_value_t = 1,
+        value_parser = value_parser!(u8).range(1..),
+        help = "Number of steps to count each files"
+    )]
+    pub step: u8,
+
+    #[arg(
+        long,
+        help = "Move the files instead of copying them to the output directory"
+    )]
+    pub r#move: bool,
+
+    #[arg(short, long, help = "Execute immediately or not")]
+    pub yes: bool,
+
+    #[arg(long, help = "Show the interleave plan without touching any files")]
+    pub dry_run: bool,
+}
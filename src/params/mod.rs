@@ -1,2 +1,5 @@
+pub mod check_sequence;
 pub mod compress;
+pub mod interleave;
 pub mod rename;
+pub mod undo;
@@ -1,5 +1,58 @@
 use crate::validation;
-use clap::{value_parser, Parser};
+use clap::{value_parser, Parser, ValueEnum};
+
+/// Order of the files to be numbered
+#[derive(Clone, Copy, ValueEnum)]
+pub enum SortKey {
+    /// Natural order of the filenames
+    Natural,
+    /// Lexical order of the filenames
+    Lexical,
+    /// Modified time
+    Mtime,
+    /// Status changed time on Unix, created time on the other platforms
+    Ctime,
+    /// File size
+    Size,
+    /// Original date in EXIF, files without it come last
+    ExifDate,
+}
+
+/// How to count the files of different extensions
+#[derive(Clone, Copy, ValueEnum)]
+pub enum CounterMode {
+    /// One counter over the natural order of the stems regardless of the extensions
+    Shared,
+    /// Separate counter for each extension
+    PerExtension,
+}
+
+/// How to put the renamed files into the output directory
+#[derive(Clone, Copy, ValueEnum)]
+pub enum OutputMode {
+    /// Copy the files
+    Copy,
+    /// Make hard links to the files
+    Hardlink,
+    /// Make copy-on-write clones of the files, which requires a supporting filesystem
+    Reflink,
+}
+
+/// Format of the rename manifest
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ManifestFormat {
+    Csv,
+    Json,
+}
+
+/// How to number the two-page spread images
+#[derive(Clone, Copy, ValueEnum)]
+pub enum SpreadMode {
+    /// Give the spread both page numbers such as `0012-0013.jpg`
+    Combined,
+    /// Give the spread the first page number and skip the second one
+    Skip,
+}
 
 #[derive(Parser)]
 /// Params for rename subcommand
@@ -28,6 +81,24 @@ pub struct RenameParams {
     )]
     pub extensions: Vec<String>,
 
+    #[arg(
+        long,
+        value_parser = validation::filepath::extension_check,
+        help = "Extensions of the sidecar files which follow the new name of the file with the same stem"
+    )]
+    pub sidecars: Vec<String>,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = CounterMode::Shared,
+        help = "How to count the files of different extensions"
+    )]
+    pub counter: CounterMode,
+
+    #[arg(long, help = "Rewrite the extensions in lowercase and `jpeg` to `jpg`")]
+    pub normalize_ext: bool,
+
     #[arg(
         short,
         long,
@@ -46,6 +117,177 @@ pub struct RenameParams {
     )]
     pub step: u8,
 
+    #[arg(
+        short,
+        long,
+        value_parser = validation::filepath::pattern_check,
+        help = "Filename pattern such as `{dir}_p{n:04}.{ext}` (placeholders: dir, parent, n, orig, ext, mtime)"
+    )]
+    pub pattern: Option<String>,
+
+    #[arg(
+        long,
+        requires = "replace",
+        conflicts_with = "pattern",
+        value_parser = validation::filepath::regex_check,
+        help = "Rename only the files whose name matches the regex"
+    )]
+    pub regex: Option<String>,
+
+    #[arg(
+        long,
+        requires = "regex",
+        value_parser = validation::filepath::replace_check,
+        help = "Filename template for --regex, which can also refer to the capture groups such as `p{1:04}.{ext}`"
+    )]
+    pub replace: Option<String>,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = SortKey::Natural,
+        help = "Order of the files to be numbered"
+    )]
+    pub sort: SortKey,
+
+    #[arg(short, long, help = "Number the files in descending order")]
+    pub reverse: bool,
+
+    #[arg(
+        long,
+        value_enum,
+        help = "Number the images wider than their neighbours as two pages"
+    )]
+    pub spreads: Option<SpreadMode>,
+
+    #[arg(
+        long,
+        default_value_t = 1.5,
+        requires = "spreads",
+        value_parser = validation::filepath::spread_ratio_check,
+        help = "Width ratio to the median width of the directory for an image to be a spread"
+    )]
+    pub spread_ratio: f64,
+
+    #[arg(
+        short,
+        long,
+        help = "Continue numbering across directories in natural order of the directory paths"
+    )]
+    pub global: bool,
+
+    #[arg(
+        long,
+        requires = "global",
+        help = "Reset the global numbering for each directory directly under the target directory"
+    )]
+    pub reset_per_subtree: bool,
+
+    #[arg(
+        long,
+        help = "Minimum depth of the directories to rename, the target directory is depth 0"
+    )]
+    pub min_depth: Option<usize>,
+
+    #[arg(
+        long,
+        help = "Maximum depth of the directories to rename, the target directory is depth 0"
+    )]
+    pub max_depth: Option<usize>,
+
+    #[arg(
+        long,
+        value_parser = validation::filepath::glob_check,
+        help = "Rename only the files matching the glob patterns by name or relative path, or in the directories matching them, and only the matching directories with --dirs"
+    )]
+    pub include: Vec<String>,
+
+    #[arg(
+        long,
+        value_parser = validation::filepath::glob_check,
+        help = "Skip the directories and files matching the glob patterns by name or relative path"
+    )]
+    pub exclude: Vec<String>,
+
+    #[arg(
+        long,
+        help = "Also rename the directories directly under the target directory to sequential numbers"
+    )]
+    pub dirs: bool,
+
+    #[arg(
+        short,
+        long,
+        conflicts_with_all = ["dirs", "insert"],
+        help = "Put the renamed files into the mirrored tree under the directory, leaving the originals untouched"
+    )]
+    pub output_dir: Option<String>,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = OutputMode::Copy,
+        requires = "output_dir",
+        help = "How to put the renamed files into the output directory"
+    )]
+    pub output_mode: OutputMode,
+
+    #[arg(
+        long,
+        requires = "at",
+        conflicts_with_all = ["pattern", "regex", "global", "dirs"],
+        value_parser = validation::filepath::file_exists,
+        help = "File in the target directory to insert into its sequentially named files"
+    )]
+    pub insert: Option<String>,
+
+    #[arg(
+        long,
+        requires = "insert",
+        help = "Number at which the file is inserted, the files from it are shifted up by the step"
+    )]
+    pub at: Option<u32>,
+
+    #[arg(
+        short,
+        long,
+        value_parser = value_parser!(u16).range(1..),
+        help = "Number of directories renamed concurrently [default: number of CPUs]"
+    )]
+    pub jobs: Option<u16>,
+
+    #[arg(
+        long,
+        default_value_t = 3,
+        help = "Number of retries when renaming a file failed"
+    )]
+    pub retries: u32,
+
+    #[arg(
+        long,
+        default_value_t = 100,
+        help = "Delay in milliseconds before the first retry, doubled on each retry"
+    )]
+    pub retry_delay: u64,
+
+    #[arg(
+        long,
+        value_enum,
+        help = "Write the original name, new name, size and SHA-256 hash of the renamed files to `.rimg-manifest.csv` or `.rimg-manifest.json` in each directory"
+    )]
+    pub manifest: Option<ManifestFormat>,
+
+    #[arg(
+        long,
+        conflicts_with_all = ["pattern", "regex", "global", "dirs", "insert", "output_dir"],
+        value_parser = validation::filepath::file_exists,
+        help = "Rename the files of the target directory by a CSV or JSON manifest with `original` and `new` columns"
+    )]
+    pub from_manifest: Option<String>,
+
     #[arg(short, long, help = "Execute immediately or not")]
     pub yes: bool,
+
+    #[arg(long, help = "Show the rename plan without renaming any files")]
+    pub dry_run: bool,
 }
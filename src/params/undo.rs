@@ -0,0 +1,15 @@
+use crate::validation;
+use clap::Parser;
+
+#[derive(Parser)]
+/// Params for undo subcommand
+pub struct UndoParams {
+    #[arg(
+        value_parser = validation::filepath::dir_exists,
+        help = "Target directory"
+    )]
+    pub input_dir: String,
+
+    #[arg(short, long, help = "Execute immediately or not")]
+    pub yes: bool,
+}
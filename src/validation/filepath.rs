@@ -1,4 +1,10 @@
-use crate::constants::file::{RAR_EXTENSION, ZIP_EXTENSION};
+use crate::constants::file::{
+    CBR_EXTENSION, CBZ_EXTENSION, EPUB_EXTENSION, PDF_EXTENSION, RAR_EXTENSION,
+    SEVEN_ZIP_EXTENSION, TAR_EXTENSION, TAR_GZ_EXTENSION, TAR_ZST_EXTENSION, ZIP_EXTENSION,
+};
+use crate::executor::filter::build_glob;
+use crate::executor::pattern::Pattern;
+use regex::Regex;
 use std::fs;
 
 /// Check dir exists or not
@@ -15,17 +21,44 @@ pub fn dir_exists(s: &str) -> Result<String, String> {
     }
 }
 
+/// Check file exists or not
+///
+/// # Arguments
+///
+/// * `s` - Given arg
+pub fn file_exists(s: &str) -> Result<String, String> {
+    let metadata = fs::metadata(s).map_err(|_| format!("`{s}` isn't a file"))?;
+    if !metadata.is_file() {
+        Err(format!("`{s}` isn't a file"))
+    } else {
+        Ok(s.to_owned())
+    }
+}
+
 /// Check format type is valid
 ///
 /// # Arguments
 ///
 /// * `s` - Given arg
 pub fn format_type_check(s: &str) -> Result<String, String> {
-    if s == RAR_EXTENSION || s == ZIP_EXTENSION {
+    if [
+        RAR_EXTENSION,
+        ZIP_EXTENSION,
+        SEVEN_ZIP_EXTENSION,
+        TAR_EXTENSION,
+        TAR_GZ_EXTENSION,
+        TAR_ZST_EXTENSION,
+        CBZ_EXTENSION,
+        CBR_EXTENSION,
+        EPUB_EXTENSION,
+        PDF_EXTENSION,
+    ]
+    .contains(&s)
+    {
         Ok(s.to_owned())
     } else {
         Err(format!(
-            "`{s}` isn't supported format type\nCurrently supports `rar` and `zip`"
+            "`{s}` isn't supported format type\nCurrently supports `rar`, `zip`, `7z`, `tar`, `tar.gz`, `tar.zst`, `cbz`, `cbr`, `epub` and `pdf`"
         ))
     }
 }
@@ -45,6 +78,57 @@ pub fn extension_check(s: &str) -> Result<String, String> {
     }
 }
 
+/// Check filename pattern is valid
+///
+/// # Arguments
+///
+/// * `s` - Given arg
+pub fn pattern_check(s: &str) -> Result<String, String> {
+    Pattern::parse(s).map(|_| s.to_owned())
+}
+
+/// Check glob pattern is valid
+///
+/// # Arguments
+///
+/// * `s` - Given arg
+pub fn glob_check(s: &str) -> Result<String, String> {
+    build_glob(s).map(|_| s.to_owned())
+}
+
+/// Check replace template of regex is valid
+///
+/// # Arguments
+///
+/// * `s` - Given arg
+pub fn replace_check(s: &str) -> Result<String, String> {
+    Pattern::parse_with_captures(s).map(|_| s.to_owned())
+}
+
+/// Check regex is valid
+///
+/// # Arguments
+///
+/// * `s` - Given arg
+pub fn regex_check(s: &str) -> Result<String, String> {
+    Regex::new(s)
+        .map(|_| s.to_owned())
+        .map_err(|e| e.to_string())
+}
+
+/// Validate whether the width ratio of spreads is greater than 1
+///
+/// # Arguments
+///
+/// * `s` - Given arg
+pub fn spread_ratio_check(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(v) if v > 1.0 && v.is_finite() => Ok(v),
+        Ok(_) => Err("Spread ratio must be greater than 1".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -68,6 +152,25 @@ mod tests {
         }
     }
 
+    mod file_exists {
+        use super::super::*;
+        use std::path::PathBuf;
+
+        #[test]
+        fn existed_file_should_return_path() {
+            let path = concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml");
+            let result = file_exists(path).unwrap();
+            assert_eq!(path, result);
+        }
+
+        #[test]
+        #[should_panic]
+        fn dir_should_panic() {
+            let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+            file_exists(String::from(path.to_string_lossy()).as_str()).unwrap();
+        }
+    }
+
     mod format_type_check {
         use super::super::*;
 
@@ -77,6 +180,24 @@ mod tests {
             assert_eq!("rar", result);
         }
 
+        #[test]
+        fn seven_zip_format_type_should_return_string() {
+            let result = format_type_check("7z").unwrap();
+            assert_eq!("7z", result);
+        }
+
+        #[test]
+        fn tar_zst_format_type_should_return_string() {
+            let result = format_type_check("tar.zst").unwrap();
+            assert_eq!("tar.zst", result);
+        }
+
+        #[test]
+        fn cbz_format_type_should_return_string() {
+            let result = format_type_check("cbz").unwrap();
+            assert_eq!("cbz", result);
+        }
+
         #[test]
         #[should_panic]
         fn invalid_format_type_should_panic() {
@@ -111,4 +232,89 @@ mod tests {
             extension_check("abcdef").unwrap();
         }
     }
+
+    mod pattern_check {
+        use super::super::*;
+
+        #[test]
+        fn valid_pattern_should_return_string() {
+            let result = pattern_check("{dir}_p{n:04}.{ext}").unwrap();
+            assert_eq!("{dir}_p{n:04}.{ext}", result);
+        }
+
+        #[test]
+        #[should_panic]
+        fn unclosed_placeholder_should_panic() {
+            pattern_check("{dir}_{n").unwrap();
+        }
+
+        #[test]
+        #[should_panic]
+        fn path_separator_should_panic() {
+            pattern_check("{dir}/{n}.{ext}").unwrap();
+        }
+    }
+
+    mod glob_check {
+        use super::super::*;
+
+        #[test]
+        fn valid_glob_should_return_string() {
+            let result = glob_check("**/_extras").unwrap();
+            assert_eq!("**/_extras", result);
+        }
+
+        #[test]
+        #[should_panic]
+        fn unclosed_class_should_panic() {
+            glob_check("cover[").unwrap();
+        }
+    }
+
+    mod replace_check {
+        use super::super::*;
+
+        #[test]
+        fn capture_group_template_should_return_string() {
+            let result = replace_check("p{1:04}.{ext}").unwrap();
+            assert_eq!("p{1:04}.{ext}", result);
+        }
+
+        #[test]
+        #[should_panic]
+        fn invalid_width_should_panic() {
+            replace_check("p{1:x}.{ext}").unwrap();
+        }
+    }
+
+    mod regex_check {
+        use super::super::*;
+
+        #[test]
+        fn valid_regex_should_return_string() {
+            let result = regex_check(r"_p(\d+)\.jpg$").unwrap();
+            assert_eq!(r"_p(\d+)\.jpg$", result);
+        }
+
+        #[test]
+        #[should_panic]
+        fn unclosed_group_should_panic() {
+            regex_check(r"_p(\d+").unwrap();
+        }
+    }
+
+    mod spread_ratio_check {
+        use super::super::*;
+
+        #[test]
+        fn ratio_above_one_should_return_value() {
+            assert_eq!(1.5, spread_ratio_check("1.5").unwrap());
+        }
+
+        #[test]
+        #[should_panic]
+        fn ratio_one_should_panic() {
+            spread_ratio_check("1").unwrap();
+        }
+    }
 }